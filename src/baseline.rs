@@ -0,0 +1,332 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cache::hash;
+use crate::dispatch::RuleInfo;
+use crate::error::{Category, CheckError, Severity};
+use crate::html::{HeadingOrder, Readability};
+use crate::ReaderConfig;
+
+pub const STALE_BASELINE: RuleInfo = RuleInfo {
+    id: "stale-baseline",
+    name: "Baseline entries still match a finding",
+    category: Category::Content,
+    default_severity: Severity::Warning,
+};
+
+/// A finding that was known when the baseline was written.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
+struct Entry {
+    rule: String,
+    /// Relative to the root of the site, so the baseline works from any checkout.
+    path: String,
+    fingerprint: String,
+    /// Kept to make the file readable, only the fingerprint is compared.
+    offender: String,
+}
+
+impl Entry {
+    fn of_finding(finding: &CheckError, root: &Path) -> Entry {
+        let path = Path::new(finding.path());
+        let path = path.strip_prefix(root).unwrap_or(path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        Entry {
+            rule: finding.rule().to_string(),
+            fingerprint: fingerprint(finding, &path),
+            path,
+            offender: finding.offender().to_string(),
+        }
+    }
+
+    fn key(&self) -> (String, String, String) {
+        (
+            self.rule.clone(),
+            self.path.clone(),
+            self.fingerprint.clone(),
+        )
+    }
+}
+
+/// Identifies a finding by what it's about rather than where it is, so it still
+/// matches after the lines above it changed. The description isn't part of it, it
+/// holds what changes without the finding being fixed, like contrast ratios or the
+/// sentence around a misspelled word.
+fn fingerprint(finding: &CheckError, path: &str) -> String {
+    let offender = match finding.rule() {
+        // the offender is the score, which changes with every edit of the page
+        Readability::ID => String::new(),
+        _ => finding
+            .offender()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let text = format!(
+        "{}\n{}\n{}\n{}",
+        finding.rule(),
+        path,
+        offender,
+        detail(finding)
+    );
+    hash(text.as_bytes())
+}
+
+/// Tells apart the findings of rules that report several problems with the same
+/// offender, by their description without the numbers that come from the page or
+/// the config.
+fn detail(finding: &CheckError) -> String {
+    match finding.rule() {
+        HeadingOrder::ID | Readability::ID => finding
+            .description()
+            .replace(|c: char| c.is_ascii_digit() || c == '.', ""),
+        _ => String::new(),
+    }
+}
+
+/// Findings that were already there when the proof reader was adopted, which
+/// don't fail a run until they're fixed.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Baseline {
+    findings: Vec<Entry>,
+}
+
+impl Baseline {
+    pub fn of_findings(findings: &[CheckError], root: &Path) -> Baseline {
+        let mut entries = findings
+            .iter()
+            .map(|f| Entry::of_finding(f, root))
+            .collect::<Vec<_>>();
+        // sorted, so rewriting the baseline gives a readable diff
+        entries.sort();
+        Baseline { findings: entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    /// Reads the baseline at `path`, `None` if there isn't one.
+    pub fn load(path: &Path) -> Result<Option<Baseline>, String> {
+        let contents = match read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Could not read baseline {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("Invalid baseline {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write(path, serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    /// Drops the findings that are in the baseline and reports the entries of
+    /// `checked` files that no longer match anything, since those were fixed.
+    pub fn apply(
+        &self,
+        findings: Vec<CheckError>,
+        checked: &[PathBuf],
+        conf: &ReaderConfig,
+    ) -> Vec<CheckError> {
+        // the same finding can be in a file more than once, so entries are counted
+        let mut known = BTreeMap::new();
+        for entry in &self.findings {
+            *known.entry(entry.key()).or_insert(0) += 1;
+        }
+
+        let mut kept = findings
+            .into_iter()
+            .filter(|finding| {
+                let entry = Entry::of_finding(finding, &conf.root_dir);
+                match known.get_mut(&entry.key()) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let severity = match conf.rule_severity(STALE_BASELINE.id, STALE_BASELINE.default_severity)
+        {
+            Some(s) => s,
+            None => return kept,
+        };
+        let checked = checked
+            .iter()
+            .map(|path| {
+                let path = path.strip_prefix(&conf.root_dir).unwrap_or(path);
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect::<HashSet<_>>();
+
+        for entry in &self.findings {
+            match known.get_mut(&entry.key()) {
+                Some(count) if *count > 0 && checked.contains(&entry.path) => *count -= 1,
+                _ => continue,
+            }
+            kept.push(CheckError::ContentError {
+                path: conf.root_dir.join(&entry.path).display().to_string(),
+                offender: entry.offender.clone(),
+                description: format!(
+                    "Baseline entry for {} no longer matches a finding",
+                    entry.rule
+                ),
+                location: None,
+                rule: STALE_BASELINE.id,
+                severity,
+            });
+        }
+
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+    use tempfile::TempDir;
+
+    fn finding(path: &str, offender: &str, line: usize) -> CheckError {
+        CheckError::AccessibilityError {
+            path: format!("public/{}", path),
+            offender: offender.to_string(),
+            description: "Image tag without alt".to_string(),
+            location: Some(Location {
+                offset: line * 10,
+                line,
+                column: 1,
+            }),
+            rule: "img-alt",
+            severity: Severity::Error,
+        }
+    }
+
+    fn setup_conf() -> ReaderConfig {
+        ReaderConfig {
+            root_dir: PathBuf::from("public"),
+            ..ReaderConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_baseline_round_trips() {
+        let dir = TempDir::new().expect("could not create temp dir");
+        let path = dir.path().join("baseline.json");
+        let baseline = Baseline::of_findings(
+            &[finding("b.html", "<img>", 3), finding("a.html", "<img>", 1)],
+            Path::new("public"),
+        );
+
+        assert_eq!(Baseline::load(&path), Ok(None));
+        baseline.save(&path).expect("could not save baseline");
+        let loaded = Baseline::load(&path)
+            .unwrap()
+            .expect("baseline should exist");
+
+        assert_eq!(loaded, baseline);
+        assert_eq!(loaded.findings[0].path, "a.html");
+    }
+
+    #[test]
+    fn test_only_new_findings_are_kept() {
+        let conf = setup_conf();
+        let baseline = Baseline::of_findings(
+            &[
+                finding("a.html", "<img  src=\"x\">", 1),
+                finding("a.html", "<img>", 2),
+            ],
+            &conf.root_dir,
+        );
+
+        // moved down the page and reformatted, but still the same finding
+        let kept = baseline.apply(
+            vec![
+                finding("a.html", "<img src=\"x\">", 8),
+                finding("a.html", "<img>", 9),
+                finding("a.html", "<img>", 10),
+            ],
+            &[PathBuf::from("public/a.html")],
+            &conf,
+        );
+
+        assert_eq!(kept, vec![finding("a.html", "<img>", 10)]);
+    }
+
+    #[test]
+    fn test_descriptions_are_not_fingerprinted() {
+        let conf = setup_conf();
+        let contrast = |description: &str| CheckError::AccessibilityError {
+            path: "public/a.html".to_string(),
+            offender: "<p> #777777 on #ffffff".to_string(),
+            description: description.to_string(),
+            location: None,
+            rule: "color-contrast",
+            severity: Severity::Error,
+        };
+        let readability = |description: &str, score: &str| CheckError::ContentError {
+            path: "public/a.html".to_string(),
+            offender: score.to_string(),
+            description: description.to_string(),
+            location: None,
+            rule: Readability::ID,
+            severity: Severity::Warning,
+        };
+        let baseline = Baseline::of_findings(
+            &[
+                contrast("Text has a contrast ratio of 4.48:1, below the minimum of 4.5:1"),
+                readability("Reading ease is below 30", "21.40"),
+            ],
+            &conf.root_dir,
+        );
+
+        // the ratio and score changed with the page, but not what's wrong with it
+        let kept = baseline.apply(
+            vec![
+                contrast("Text has a contrast ratio of 4.21:1, below the minimum of 4.5:1"),
+                readability("Reading ease is below 30", "25.10"),
+                readability("Sentences are longer than 25 words on average", "27.00"),
+            ],
+            &[PathBuf::from("public/a.html")],
+            &conf,
+        );
+
+        assert_eq!(
+            kept,
+            vec![readability(
+                "Sentences are longer than 25 words on average",
+                "27.00"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_fixed_entries_are_stale() {
+        let conf = setup_conf();
+        let baseline = Baseline::of_findings(
+            &[finding("a.html", "<img>", 1), finding("b.html", "<img>", 1)],
+            &conf.root_dir,
+        );
+
+        // b.html wasn't checked this time, so it can't tell if its entry is fixed
+        let kept = baseline.apply(vec![], &[PathBuf::from("public/a.html")], &conf);
+
+        assert_eq!(
+            kept,
+            vec![CheckError::ContentError {
+                path: "public/a.html".to_string(),
+                offender: "<img>".to_string(),
+                description: "Baseline entry for img-alt no longer matches a finding".to_string(),
+                location: None,
+                rule: STALE_BASELINE.id,
+                severity: Severity::Warning,
+            }]
+        );
+    }
+}
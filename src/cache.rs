@@ -0,0 +1,428 @@
+use clap::crate_version;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{read, read_dir, read_to_string, write};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::date::{today, DateRule};
+use crate::dispatch::rule_ids;
+use crate::error::{CheckError, Severity};
+use crate::location::Location;
+use crate::site::Site;
+use crate::ReaderConfig;
+
+/// FNV-1a of `bytes`, which unlike the std hasher is guaranteed to stay the
+/// same between releases.
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// The names of the Hunspell files in `dir` along with a hash of their contents,
+/// which are too big to be part of the key themselves.
+fn dictionary_hashes(dir: &Path) -> String {
+    let mut files = read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "aff" || e == "dic"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+        .iter()
+        .map(|p| {
+            let contents = read(p).map(|c| hash(&c)).unwrap_or_default();
+            format!("{} {}", p.display(), contents)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    ForbiddenFile,
+    Content,
+    Accessibility,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Finding {
+    kind: Kind,
+    path: String,
+    offender: String,
+    description: String,
+    location: Option<Location>,
+    rule: String,
+    severity: Severity,
+}
+
+impl Finding {
+    /// `None` for IO errors, files that couldn't be read are checked again.
+    fn of_error(error: &CheckError) -> Option<Finding> {
+        let kind = match error {
+            CheckError::ForbiddenFile { .. } => Kind::ForbiddenFile,
+            CheckError::ContentError { .. } => Kind::Content,
+            CheckError::AccessibilityError { .. } => Kind::Accessibility,
+            CheckError::Io { .. } => return None,
+        };
+        Some(Finding {
+            kind,
+            path: error.path().to_string(),
+            offender: error.offender().to_string(),
+            description: error.description(),
+            location: error.location(),
+            rule: error.rule().to_string(),
+            severity: error.severity(),
+        })
+    }
+
+    /// `None` if the rule is no longer known.
+    fn to_error(&self, rules: &[&'static str]) -> Option<CheckError> {
+        let rule = *rules.iter().find(|r| **r == self.rule)?;
+        let (path, offender, description) = (
+            self.path.clone(),
+            self.offender.clone(),
+            self.description.clone(),
+        );
+        let (location, severity) = (self.location, self.severity);
+        Some(match self.kind {
+            Kind::ForbiddenFile => CheckError::ForbiddenFile {
+                path,
+                rule,
+                severity,
+            },
+            Kind::Content => CheckError::ContentError {
+                path,
+                offender,
+                description,
+                location,
+                rule,
+                severity,
+            },
+            Kind::Accessibility => CheckError::AccessibilityError {
+                path,
+                offender,
+                description,
+                location,
+                rule,
+                severity,
+            },
+        })
+    }
+}
+
+/// What's known about a checked file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FileEntry {
+    hash: String,
+    /// The digest of every file the findings depend on, like link targets.
+    dependencies: BTreeMap<PathBuf, String>,
+    findings: Vec<Finding>,
+}
+
+/// The anchors of a page, for the site index.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PageEntry {
+    hash: String,
+    anchors: HashSet<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct CacheContents {
+    /// Hash of the config and the rules, a different one throws everything away.
+    key: String,
+    pages: HashMap<PathBuf, PageEntry>,
+    files: HashMap<PathBuf, FileEntry>,
+}
+
+/// Findings of earlier runs, so files that didn't change, and neither did the
+/// files they depend on, don't have to be checked again.
+pub struct Cache {
+    path: PathBuf,
+    rules: Vec<&'static str>,
+    old: CacheContents,
+    new: Mutex<CacheContents>,
+}
+
+impl Cache {
+    /// A missing, unreadable or outdated cache file just means starting over.
+    pub fn load(path: &Path, conf: &ReaderConfig) -> Cache {
+        let mut rules = rule_ids();
+        rules.extend(conf.style_rules.iter().map(|r| r.id));
+
+        // the word list and dictionaries aren't part of the config, but do change what's reported
+        let word_list = conf
+            .spelling
+            .word_list
+            .as_deref()
+            .and_then(|p| read_to_string(p).ok())
+            .unwrap_or_default();
+        let dictionaries = conf
+            .spelling
+            .dictionaries
+            .as_deref()
+            .map(dictionary_hashes)
+            .unwrap_or_default();
+        // relative forbidden dates move every day, and what they found with them
+        let today = if conf.date_rules.iter().any(DateRule::uses_today) {
+            today().to_string()
+        } else {
+            String::new()
+        };
+        let key = hash(
+            format!(
+                "{}\n{}\n{:?}\n{}\n{}\n{}",
+                crate_version!(),
+                rules.join(","),
+                conf,
+                word_list,
+                dictionaries,
+                today
+            )
+            .as_bytes(),
+        );
+
+        let old = read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<CacheContents>(&contents).ok())
+            .filter(|old| old.key == key)
+            .unwrap_or_default();
+
+        Cache {
+            path: path.to_path_buf(),
+            rules,
+            old,
+            new: Mutex::new(CacheContents {
+                key,
+                ..CacheContents::default()
+            }),
+        }
+    }
+
+    /// The anchors of the page at `path` as of the last run, if it didn't change since.
+    pub fn anchors(&self, path: &Path, contents: &str) -> Option<HashSet<String>> {
+        let entry = self.old.pages.get(path)?;
+        let hash = hash(contents.as_bytes());
+        if entry.hash != hash {
+            return None;
+        }
+        self.new
+            .lock()
+            .unwrap()
+            .pages
+            .insert(path.to_path_buf(), entry.clone());
+        Some(entry.anchors.clone())
+    }
+
+    /// Keeps the anchors of every page of the site for the next run.
+    pub fn index_pages(&self, site: &Site) {
+        let mut new = self.new.lock().unwrap();
+        for (path, anchors) in site.pages() {
+            if new.pages.contains_key(path) {
+                continue;
+            }
+            if let Ok(contents) = read(path) {
+                let entry = PageEntry {
+                    hash: hash(&contents),
+                    anchors: anchors.clone(),
+                };
+                new.pages.insert(path.clone(), entry);
+            }
+        }
+    }
+
+    /// The findings of the file at `path` as of the last run, if neither it nor
+    /// what it depends on changed since.
+    pub fn cached(&self, path: &Path, site: &Site) -> Option<Vec<CheckError>> {
+        let hash = hash(&read(path).ok()?);
+        let entry = self.old.files.get(path).filter(|e| e.hash == hash)?;
+        let unchanged = entry
+            .dependencies
+            .iter()
+            .all(|(dependency, digest)| site.digest(dependency) == *digest);
+        if !unchanged {
+            return None;
+        }
+        let findings = entry
+            .findings
+            .iter()
+            .map(|f| f.to_error(&self.rules))
+            .collect::<Option<Vec<_>>>()?;
+        let mut new = self.new.lock().unwrap();
+        new.files.insert(path.to_path_buf(), entry.clone());
+        Some(findings)
+    }
+
+    /// The findings of the file at `path`, from the cache if it and what it
+    /// depends on didn't change, otherwise from running `check`.
+    pub fn check<F>(&self, path: &Path, site: &Site, check: F) -> Vec<CheckError>
+    where
+        F: FnOnce() -> Vec<CheckError>,
+    {
+        if let Some(findings) = self.cached(path, site) {
+            return findings;
+        }
+        let hash = match read(path) {
+            Ok(contents) => hash(&contents),
+            // reading it again will report the error
+            Err(_) => return check(),
+        };
+
+        let errors = check();
+        let dependencies = site
+            .take_dependencies(path)
+            .into_iter()
+            .map(|dependency| {
+                let digest = site.digest(&dependency);
+                (dependency, digest)
+            })
+            .collect();
+        let findings = errors
+            .iter()
+            .map(Finding::of_error)
+            .collect::<Option<Vec<_>>>();
+        if let Some(findings) = findings {
+            let entry = FileEntry {
+                hash,
+                dependencies,
+                findings,
+            };
+            let mut new = self.new.lock().unwrap();
+            new.files.insert(path.to_path_buf(), entry);
+        }
+        errors
+    }
+
+    /// Writes what's known about the files of this run, forgetting the others.
+    pub fn save(&self) -> io::Result<()> {
+        let new = self.new.lock().unwrap();
+        write(&self.path, serde_json::to_string(&*new)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::check_file;
+    use std::cell::Cell;
+    use tempfile::TempDir;
+
+    fn setup_site() -> TempDir {
+        let site_dir = TempDir::new().expect("could not create temp dir");
+        write(
+            site_dir.path().join("index.html"),
+            r##"<html lang="en"><head><title>Home</title></head><body><main><h1>Home</h1>
+            <a href="about.html#team">Team</a><img src="logo.png"></main></body></html>"##,
+        )
+        .expect("failed to write file");
+        write(
+            site_dir.path().join("about.html"),
+            r#"<html lang="en"><head><title>About</title></head><body><main><h1 id="team">Team</h1></main></body></html>"#,
+        )
+        .expect("failed to write file");
+        site_dir
+    }
+
+    /// Checks `index.html` with a fresh cache loaded from disk, the way a new run
+    /// would, returning its findings and whether it was actually checked.
+    fn run(site_dir: &TempDir, conf: &ReaderConfig) -> (Vec<String>, bool) {
+        let cache = Cache::load(&conf.cache_file, conf);
+        let site = Site::index_with(site_dir.path(), |path, contents| {
+            cache.anchors(path, contents)
+        });
+        cache.index_pages(&site);
+        let path = site_dir.path().join("index.html");
+        let checked = Cell::new(false);
+        let errors = cache.check(&path, &site, || {
+            checked.set(true);
+            check_file(&path, conf, &site)
+        });
+        cache.save().expect("could not save cache");
+        let rules = errors.iter().map(|e| e.rule().to_string()).collect();
+        (rules, checked.get())
+    }
+
+    #[test]
+    fn test_unchanged_files_are_not_checked_again() {
+        let site_dir = setup_site();
+        let conf = ReaderConfig {
+            root_dir: site_dir.path().to_path_buf(),
+            cache_file: site_dir.path().join("cache.json"),
+            ..ReaderConfig::default()
+        };
+
+        let (first, checked) = run(&site_dir, &conf);
+        assert!(checked);
+        assert_eq!(first, vec!["img-alt", "internal-link"]);
+
+        let (second, checked) = run(&site_dir, &conf);
+        assert!(!checked);
+        assert_eq!(second, first);
+
+        // a different config throws the cache away
+        let other_conf = ReaderConfig {
+            max_important: 3,
+            ..conf
+        };
+        let (_, checked) = run(&site_dir, &other_conf);
+        assert!(checked);
+    }
+
+    #[test]
+    fn test_changed_dictionaries_throw_the_cache_away() {
+        let site_dir = setup_site();
+        let dictionaries = TempDir::new().expect("could not create temp dir");
+        write(dictionaries.path().join("en.aff"), "SET UTF-8\n").expect("failed to write file");
+        write(dictionaries.path().join("en.dic"), "1\nhome\n").expect("failed to write file");
+        let mut conf = ReaderConfig {
+            root_dir: site_dir.path().to_path_buf(),
+            cache_file: site_dir.path().join("cache.json"),
+            ..ReaderConfig::default()
+        };
+        conf.spelling.dictionaries = Some(dictionaries.path().to_path_buf());
+
+        run(&site_dir, &conf);
+        let (_, checked) = run(&site_dir, &conf);
+        assert!(!checked);
+
+        // the config still points at the same folder, but the words in it changed
+        write(dictionaries.path().join("en.dic"), "2\nhome\nteam\n").expect("failed to write file");
+        let (_, checked) = run(&site_dir, &conf);
+        assert!(checked);
+    }
+
+    #[test]
+    fn test_changed_link_targets_are_checked_again() {
+        let site_dir = setup_site();
+        let conf = ReaderConfig {
+            root_dir: site_dir.path().to_path_buf(),
+            cache_file: site_dir.path().join("cache.json"),
+            ..ReaderConfig::default()
+        };
+        run(&site_dir, &conf);
+
+        // index.html is the same, but the anchor it links to is gone
+        write(
+            site_dir.path().join("about.html"),
+            r#"<html lang="en"><head><title>About</title></head><body><main><h1>Us</h1></main></body></html>"#,
+        )
+        .expect("failed to write file");
+        let (rules, checked) = run(&site_dir, &conf);
+        assert!(checked);
+        assert_eq!(rules, vec!["img-alt", "internal-link", "internal-fragment"]);
+
+        // and so is a file it links to
+        write(site_dir.path().join("logo.png"), "").expect("failed to write file");
+        let (rules, checked) = run(&site_dir, &conf);
+        assert!(checked);
+        assert_eq!(rules, vec!["img-alt", "internal-fragment"]);
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use std::fs::canonicalize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::Generator;
+use crate::dispatch::CHECKED_EXTENSIONS;
+use crate::ReaderConfig;
+
+/// Which files need all their checks run, after mapping changed sources to pages.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Scope {
+    Files(HashSet<PathBuf>),
+    /// A change could affect any page, like one to a template.
+    Everything(String),
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Every file that changed since `rev`, uncommitted changes and new files that
+/// aren't ignored included, as absolute paths.
+pub fn changed_since(rev: &str) -> Result<Vec<PathBuf>, String> {
+    changed_in(Path::new("."), rev)
+}
+
+fn changed_in(dir: &Path, rev: &str) -> Result<Vec<PathBuf>, String> {
+    let top_level = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let top_level = Path::new(top_level.trim());
+    // both list paths relative to the top level, as long as they're run from there
+    let changed = git(
+        top_level,
+        &["diff", "--name-only", "--no-renames", rev, "--"],
+    )?;
+    let untracked = git(top_level, &["ls-files", "--others", "--exclude-standard"])?;
+    Ok(changed
+        .lines()
+        .chain(untracked.lines())
+        .map(|line| top_level.join(line))
+        .collect())
+}
+
+/// The pages a generator builds from the markdown source at `relative`, which is
+/// relative to the folder it keeps its sources in. Several when it could be either.
+fn output_candidates(relative: &Path, generator: Generator) -> Vec<PathBuf> {
+    let parent = relative.parent().unwrap_or_else(|| Path::new(""));
+    let stem = relative.file_stem().unwrap_or_default();
+    if stem == "_index" || stem == "index" {
+        return vec![parent.join("index.html")];
+    }
+    match generator {
+        Generator::Zola | Generator::Hugo => vec![parent.join(stem).join("index.html")],
+        Generator::Jekyll => vec![
+            parent.join(stem).with_extension("html"),
+            parent.join(stem).join("index.html"),
+        ],
+    }
+}
+
+/// Maps the `changed` files to the files under `root_dir` they affect.
+pub fn affected_files(changed: &[PathBuf], conf: &ReaderConfig) -> Scope {
+    let root = match canonicalize(&conf.root_dir) {
+        Ok(r) => r,
+        Err(e) => return Scope::Everything(format!("can't find the root folder: {}", e)),
+    };
+
+    let mut files = HashSet::new();
+    for path in changed {
+        let is_checked = path
+            .extension()
+            .is_some_and(|e| CHECKED_EXTENSIONS.iter().any(|c| e == *c));
+        let is_markdown = path
+            .extension()
+            .is_some_and(|e| e == "md" || e == "markdown");
+
+        if let Ok(relative) = path.strip_prefix(&root) {
+            // other files in the site, like images, are only linked to,
+            // which the link checks on every page cover
+            if is_checked && path.exists() {
+                files.insert(conf.root_dir.join(relative));
+            }
+            continue;
+        }
+        // a deleted source only leaves links to it broken, which are checked anyway
+        if is_markdown && !path.exists() {
+            continue;
+        }
+
+        let is_post = path.components().any(|c| c.as_os_str() == "_posts");
+        let relative = match conf.generator {
+            _ if !is_markdown => None,
+            Generator::Zola | Generator::Hugo => path
+                .ancestors()
+                .find(|p| p.file_name().is_some_and(|name| name == "content"))
+                .and_then(|content| path.strip_prefix(content).ok()),
+            // the permalink decides where posts end up
+            Generator::Jekyll if is_post => None,
+            // jekyll keeps its sources next to the folder it builds into
+            Generator::Jekyll => root
+                .parent()
+                .and_then(|source| path.strip_prefix(source).ok()),
+        };
+        let relative = match relative {
+            Some(r) => r,
+            None => {
+                return Scope::Everything(format!("{} can change any page", path.display()));
+            }
+        };
+        // with a slug or permalink in the front matter the page is somewhere else
+        match output_candidates(relative, conf.generator)
+            .into_iter()
+            .find(|candidate| root.join(candidate).is_file())
+        {
+            Some(page) => {
+                files.insert(conf.root_dir.join(page));
+            }
+            None => {
+                return Scope::Everything(format!("no page found for {}", path.display()));
+            }
+        }
+    }
+    Scope::Files(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    fn setup_project(files: &[&str]) -> TempDir {
+        let project = TempDir::new().expect("could not create temp dir");
+        for file in files {
+            let path = project.path().join(file);
+            create_dir_all(path.parent().unwrap()).expect("failed to create folder");
+            write(path, "").expect("failed to write file");
+        }
+        project
+    }
+
+    fn setup_conf(project: &TempDir, generator: Generator) -> ReaderConfig {
+        ReaderConfig {
+            root_dir: canonicalize(project.path()).unwrap().join("public"),
+            generator,
+            ..ReaderConfig::default()
+        }
+    }
+
+    fn changed(project: &TempDir, files: &[&str]) -> Vec<PathBuf> {
+        let project = canonicalize(project.path()).unwrap();
+        files.iter().map(|f| project.join(f)).collect()
+    }
+
+    fn files(conf: &ReaderConfig, files: &[&str]) -> Scope {
+        Scope::Files(files.iter().map(|f| conf.root_dir.join(f)).collect())
+    }
+
+    #[test]
+    fn test_maps_zola_sources_to_pages() {
+        let project = setup_project(&[
+            "content/blog/_index.md",
+            "content/blog/post.md",
+            "content/about/index.md",
+            "public/blog/index.html",
+            "public/blog/post/index.html",
+            "public/about/index.html",
+            "public/style.css",
+            "public/logo.png",
+        ]);
+        let conf = setup_conf(&project, Generator::Zola);
+
+        let scope = affected_files(
+            &changed(
+                &project,
+                &[
+                    "content/blog/_index.md",
+                    "content/blog/post.md",
+                    "content/about/index.md",
+                    "content/deleted.md",
+                    "public/style.css",
+                    "public/logo.png",
+                ],
+            ),
+            &conf,
+        );
+
+        assert_eq!(
+            scope,
+            files(
+                &conf,
+                &[
+                    "blog/index.html",
+                    "blog/post/index.html",
+                    "about/index.html",
+                    "style.css"
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_maps_jekyll_sources_to_pages() {
+        let project =
+            setup_project(&["about.md", "public/about.html", "_posts/2021-04-13-post.md"]);
+        let conf = setup_conf(&project, Generator::Jekyll);
+
+        let scope = affected_files(&changed(&project, &["about.md"]), &conf);
+        assert_eq!(scope, files(&conf, &["about.html"]));
+
+        let scope = affected_files(&changed(&project, &["_posts/2021-04-13-post.md"]), &conf);
+        assert!(matches!(scope, Scope::Everything(_)));
+    }
+
+    #[test]
+    fn test_lists_changed_and_untracked_files() {
+        let project = setup_project(&["content/post.md", ".gitignore"]);
+        let run = |args: &[&str]| git(project.path(), args).expect("git failed");
+        write(project.path().join(".gitignore"), "public/\n").expect("failed to write file");
+        run(&["init", "-q"]);
+        run(&["add", "."]);
+        run(&[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-qm",
+            "initial",
+        ]);
+
+        write(project.path().join("content/post.md"), "# Post").expect("failed to write file");
+        write(project.path().join("content/new.md"), "# New").expect("failed to write file");
+        create_dir_all(project.path().join("public")).expect("failed to create folder");
+        write(project.path().join("public/index.html"), "").expect("failed to write file");
+
+        // run from a subfolder, paths are still relative to the top level
+        let mut res = changed_in(&project.path().join("content"), "HEAD").expect("git failed");
+        res.sort();
+
+        assert_eq!(
+            res,
+            changed(&project, &["content/new.md", "content/post.md"])
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_everything() {
+        let project = setup_project(&[
+            "templates/page.html",
+            "content/moved.md",
+            "public/index.html",
+        ]);
+        let conf = setup_conf(&project, Generator::Zola);
+
+        let scope = affected_files(&changed(&project, &["templates/page.html"]), &conf);
+        assert!(matches!(scope, Scope::Everything(_)));
+
+        // its front matter must have moved it, so it's not where it would usually be
+        let scope = affected_files(&changed(&project, &["content/moved.md"]), &conf);
+        assert!(matches!(scope, Scope::Everything(_)));
+    }
+}
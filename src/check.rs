@@ -0,0 +1,83 @@
+use crate::error::{Category, CheckError, Severity};
+use crate::ReaderConfig;
+
+/// Every violation a check found, an empty list means the check passed.
+pub type CheckResult = Vec<CheckError>;
+
+/// What's known about a rule without running it.
+pub trait Rule: Sync {
+    /// Stable identifier used to refer to the rule from the config file and in reports.
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    fn category(&self) -> Category;
+    fn default_severity(&self) -> Severity;
+}
+
+/// A single rule that can be run against a parsed file, like a `Page` or a `Stylesheet`.
+pub trait Check<T>: Rule {
+    fn check(&self, input: &T, conf: &ReaderConfig) -> CheckResult;
+}
+
+// Declares a unit struct implementing `Check` on `$input`, which is generic over
+// the lifetime of its source, for one of the check functions of a module.
+macro_rules! check {
+    ($input:ident, $rule:ident, $id:expr, $name:expr, $category:ident, $severity:ident, |$arg:ident, $conf:ident| $body:expr) => {
+        pub struct $rule;
+
+        impl $rule {
+            pub const ID: &'static str = $id;
+        }
+
+        impl crate::check::Rule for $rule {
+            fn id(&self) -> &'static str {
+                Self::ID
+            }
+            fn name(&self) -> &'static str {
+                $name
+            }
+            fn category(&self) -> crate::error::Category {
+                crate::error::Category::$category
+            }
+            fn default_severity(&self) -> crate::error::Severity {
+                crate::error::Severity::$severity
+            }
+        }
+
+        impl<'a> crate::check::Check<$input<'a>> for $rule {
+            fn check(
+                &self,
+                $arg: &$input<'a>,
+                $conf: &crate::ReaderConfig,
+            ) -> crate::check::CheckResult {
+                $body
+            }
+        }
+    };
+}
+
+/// Runs the `rules` that aren't turned off against `input`, returning what they
+/// found with the configured severity, along with the ids of the rules that ran.
+pub fn run_checks<T, R>(
+    rules: &[&R],
+    input: &T,
+    conf: &ReaderConfig,
+) -> (CheckResult, Vec<&'static str>)
+where
+    R: Check<T> + ?Sized,
+{
+    let mut errors = vec![];
+    let mut ran = vec![];
+    for rule in rules {
+        let severity = match conf.rule_severity(rule.id(), rule.default_severity()) {
+            Some(s) => s,
+            None => continue,
+        };
+        ran.push(rule.id());
+        errors.extend(
+            rule.check(input, conf)
+                .into_iter()
+                .map(|err| err.with_severity(severity)),
+        );
+    }
+    (errors, ran)
+}
@@ -1,7 +1,7 @@
 use clap::{crate_authors, crate_description, crate_version, App, Arg};
 
 pub fn build_cli() -> App<'static, 'static> {
-    App::new("proof-reader")
+    let app = App::new("proof-reader")
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
@@ -15,13 +15,15 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Root of the website to check")
                 .required(true),
         )
-        // .arg(
-        //     Arg::with_name("exclude")
-        //         .short("e")
-        //         .long("exclude")
-        //         .takes_value(true)
-        //         .help("Directory to exclude from search"),
-        // )
+        .arg(
+            Arg::with_name("exclude")
+                .short("e")
+                .long("exclude")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Glob of files or directories to exclude from search, can be repeated"),
+        )
         .arg(
             Arg::with_name("config")
                 .short("c")
@@ -30,4 +32,62 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Config file")
                 .default_value("./config.toml"),
         )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["human", "json", "sarif", "junit"])
+                .default_value("human")
+                .help("Output format of the findings"),
+        )
+        .arg(
+            Arg::with_name("changed-since")
+                .long("changed-since")
+                .takes_value(true)
+                .value_name("rev")
+                .conflicts_with("write-baseline")
+                .help("Only run every check on pages built from files changed since this git revision"),
+        )
+        .arg(
+            Arg::with_name("no-cache")
+                .long("no-cache")
+                .help("Check every file, instead of reusing the findings of unchanged ones"),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .help("Baseline file, overrides baseline_file from the config"),
+        )
+        .arg(
+            Arg::with_name("write-baseline")
+                .long("write-baseline")
+                .help("Record the current findings in the baseline file and exit"),
+        )
+        .arg(
+            Arg::with_name("readability")
+                .long("readability")
+                .help("Add the readability of every page to the JSON output"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .conflicts_with_all(&["changed-since", "write-baseline", "readability"])
+                .help("Check again whenever files change, showing the problems there are now"),
+        )
+        .arg(
+            Arg::with_name("list-rules")
+                .long("list-rules")
+                .help("List all available rules and exit"),
+        );
+
+    #[cfg(feature = "external-links")]
+    let app = app.arg(
+        Arg::with_name("external-links")
+            .long("external-links")
+            .help("Also check that links to other sites resolve"),
+    );
+
+    app
 }
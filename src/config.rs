@@ -0,0 +1,718 @@
+use regex::{Regex, RegexBuilder};
+use scraper::Selector;
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::date::DateRule;
+use crate::dispatch::rule_ids;
+use crate::error::{ConfigError, Severity};
+
+/// Matches the tag name in links like `/tags/wip`, the way Zola lays out taxonomies.
+pub const DEFAULT_TAG_URL_PATTERN: &str = "tags/([-a-zA-Z0-9]+)";
+
+/// How a rule is treated, as set in the `[rules]` table of the config file.
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    Off,
+    Warn,
+    Error,
+}
+
+/// The static site generator markdown sources are written for, which decides
+/// where their front matter keeps dates, tags and drafts. Only markdown sources
+/// have front matter, built pages are still read through their html.
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Generator {
+    Zola,
+    Hugo,
+    Jekyll,
+}
+
+/// Settings for checking links to other sites, used when built with the
+/// `external-links` feature and run with `--external-links`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExternalLinksConfig {
+    /// Only URLs matching one of these regexes are checked, all of them if empty.
+    pub allow: Vec<String>,
+    /// URLs matching any of these regexes are never requested.
+    pub deny: Vec<String>,
+    /// Maximum number of requests in flight at once.
+    pub concurrency: usize,
+    /// Minimum time between two requests to the same host.
+    pub host_interval_ms: u64,
+    /// How often a request that failed or got a 429 or 5xx is tried again.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every retry after it.
+    pub backoff_ms: u64,
+    pub timeout_secs: u64,
+    /// Where results are kept between runs.
+    pub cache_file: PathBuf,
+    /// How long a cached result is trusted, 0 turns the cache off.
+    pub cache_ttl_hours: u64,
+}
+
+impl Default for ExternalLinksConfig {
+    fn default() -> Self {
+        ExternalLinksConfig {
+            allow: vec![],
+            deny: vec![],
+            concurrency: 8,
+            host_interval_ms: 500,
+            retries: 2,
+            backoff_ms: 1000,
+            timeout_secs: 10,
+            cache_file: PathBuf::from(".proof-reader-links.json"),
+            cache_ttl_hours: 24,
+        }
+    }
+}
+
+/// Settings for the spelling rule.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpellingConfig {
+    /// Folder with Hunspell dictionaries named like `en_US.aff` and `en_US.dic`.
+    /// None are bundled, so spelling isn't checked until this is set.
+    pub dictionaries: Option<PathBuf>,
+    /// File listing words that are spelled correctly in this project, one per line.
+    pub word_list: Option<PathBuf>,
+}
+
+/// Thresholds for the readability rule.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadabilityConfig {
+    /// Pages with fewer words than this are too short to score.
+    pub min_words: usize,
+    /// Lowest Flesch reading ease allowed, for languages there's a formula for.
+    pub min_reading_ease: f64,
+    /// Most words a sentence may have on average.
+    pub max_sentence_length: f64,
+    /// Largest share of sentences in the passive voice, from 0 to 1. Only English is checked.
+    pub max_passive_ratio: f64,
+}
+
+impl Default for ReadabilityConfig {
+    fn default() -> Self {
+        ReadabilityConfig {
+            min_words: 100,
+            min_reading_ease: 30.0,
+            max_sentence_length: 25.0,
+            max_passive_ratio: 0.25,
+        }
+    }
+}
+
+/// A rule from the editors' style guide, checked against the prose of every page.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StyleRule {
+    /// Reported as the rule of findings, and used to configure or suppress it.
+    #[serde(deserialize_with = "leak_id")]
+    pub id: StyleRuleId,
+    pub message: String,
+    /// Regex for text that goes against the style guide.
+    pub pattern: Option<String>,
+    /// Words or phrases to avoid, each with what to write instead.
+    #[serde(default)]
+    pub replace: BTreeMap<String, String>,
+    /// Only the text of elements matching this selector is checked, instead of
+    /// the prose in `main` and `article`.
+    pub selector: Option<String>,
+    #[serde(default = "default_style_level")]
+    pub severity: RuleLevel,
+    /// The text the rule reports, `pattern` or any of the words in `replace`,
+    /// compiled by `validate`.
+    #[serde(skip)]
+    pub matcher: Option<Regex>,
+    /// `selector` parsed, by `validate`.
+    #[serde(skip)]
+    pub parsed_selector: Option<Selector>,
+}
+
+impl StyleRule {
+    fn compile_matcher(&self) -> Result<Regex, regex::Error> {
+        if let Some(pattern) = &self.pattern {
+            return Regex::new(pattern);
+        }
+        // longest first, so "in order to" wins over "in order"
+        let mut words = self.replace.keys().collect::<Vec<_>>();
+        words.sort_by_key(|w| std::cmp::Reverse(w.len()));
+        let alternatives = words
+            .iter()
+            .map(|w| regex::escape(w))
+            .collect::<Vec<_>>()
+            .join("|");
+        RegexBuilder::new(&format!(r"\b(?:{})\b", alternatives))
+            .case_insensitive(true)
+            .build()
+    }
+}
+
+fn default_style_level() -> RuleLevel {
+    RuleLevel::Warn
+}
+
+/// Rule ids are `&'static str` everywhere, the config is only read once so
+/// leaking the ones it defines is fine. Named so that serde doesn't take the
+/// field for one borrowed from the config file.
+pub type StyleRuleId = &'static str;
+
+fn leak_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<StyleRuleId, D::Error> {
+    String::deserialize(deserializer).map(|s| &*Box::leak(s.into_boxed_str()))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReaderConfig {
+    pub root_dir: PathBuf,
+    pub excluded_file_glob: Option<String>,
+    /// Only files matching one of these globs are checked, all of them if empty.
+    pub include_globs: Vec<String>,
+    /// Files and folders matching any of these globs are skipped.
+    pub exclude_globs: Vec<String>,
+    pub forbidden_tags: Vec<String>,
+    pub forbidden_folders: Vec<String>,
+    pub forbidden_dates: Vec<String>,
+    /// `forbidden_dates` parsed, by `validate`.
+    #[serde(skip)]
+    pub date_rules: Vec<DateRule>,
+    /// Element holding the publish date of built pages. Markdown sources are
+    /// checked against their front matter instead, built pages have none left.
+    pub pub_date_selector: Option<String>,
+    /// Regex applied to the links in `div.tags` of built pages, the first capture
+    /// group is the tag name. Markdown sources use the tags in their front matter.
+    pub tag_url_pattern: String,
+    /// `tag_url_pattern` compiled, by `validate`.
+    #[serde(skip)]
+    pub tag_url_regex: Regex,
+    pub generator: Generator,
+    /// How many `!important`s a stylesheet may have before it's reported.
+    pub max_important: usize,
+    /// Overrides of the default severity of rules, keyed by rule id.
+    pub rules: BTreeMap<String, RuleLevel>,
+    pub external_links: ExternalLinksConfig,
+    pub spelling: SpellingConfig,
+    pub style_rules: Vec<StyleRule>,
+    pub readability: ReadabilityConfig,
+    /// Findings in this file don't fail a run, see `--write-baseline`.
+    pub baseline_file: PathBuf,
+    /// Where the findings of unchanged files are kept between runs.
+    pub cache_file: PathBuf,
+}
+
+impl Default for ReaderConfig {
+    fn default() -> Self {
+        ReaderConfig {
+            root_dir: PathBuf::from("./public"),
+            excluded_file_glob: None,
+            include_globs: vec![],
+            exclude_globs: vec![],
+            forbidden_tags: vec!["wip".to_string()],
+            forbidden_folders: vec!["unpublished".to_string(), "publish-queue".to_string()],
+            forbidden_dates: vec![],
+            date_rules: vec![],
+            pub_date_selector: None,
+            tag_url_pattern: DEFAULT_TAG_URL_PATTERN.to_string(),
+            tag_url_regex: Regex::new(DEFAULT_TAG_URL_PATTERN).unwrap(),
+            generator: Generator::Zola,
+            max_important: 10,
+            rules: BTreeMap::new(),
+            external_links: ExternalLinksConfig::default(),
+            spelling: SpellingConfig::default(),
+            style_rules: vec![],
+            readability: ReadabilityConfig::default(),
+            baseline_file: PathBuf::from(".proof-reader-baseline.json"),
+            cache_file: PathBuf::from(".proof-reader-cache.json"),
+        }
+    }
+}
+
+impl ReaderConfig {
+    /// The severity findings of a rule should be reported with,
+    /// or `None` if the rule is turned off.
+    pub fn rule_severity(&self, rule_id: &str, default: Severity) -> Option<Severity> {
+        match self.rules.get(rule_id) {
+            None => Some(default),
+            Some(RuleLevel::Off) => None,
+            Some(RuleLevel::Warn) => Some(Severity::Warning),
+            Some(RuleLevel::Error) => Some(Severity::Error),
+        }
+    }
+
+    /// All exclude globs from the config file, `excluded_file_glob` included.
+    pub fn excludes(&self) -> Vec<String> {
+        self.excluded_file_glob
+            .iter()
+            .chain(self.exclude_globs.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Checks the values that serde can't check for us, so that a typo in
+    /// the config file is reported up front instead of silently disabling a check.
+    /// Regexes are kept compiled, so they're only compiled once.
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
+        if self.root_dir.as_os_str().is_empty() {
+            return Err(invalid("root_dir", "", "must not be empty"));
+        }
+
+        if let Some(pattern) = &self.excluded_file_glob {
+            if let Err(e) = glob::Pattern::new(pattern) {
+                return Err(invalid("excluded_file_glob", pattern, &e.to_string()));
+            }
+        }
+
+        for (name, globs) in &[
+            ("include_globs", &self.include_globs),
+            ("exclude_globs", &self.exclude_globs),
+        ] {
+            for (i, pattern) in globs.iter().enumerate() {
+                if let Err(e) = glob::Pattern::new(pattern) {
+                    let key = format!("{}[{}]", name, i);
+                    return Err(invalid(&key, pattern, &e.to_string()));
+                }
+            }
+        }
+
+        for (i, tag) in self.forbidden_tags.iter().enumerate() {
+            if tag.trim().is_empty() {
+                let key = format!("forbidden_tags[{}]", i);
+                return Err(invalid(&key, tag, "must not be empty"));
+            }
+        }
+
+        for (i, folder) in self.forbidden_folders.iter().enumerate() {
+            if folder.trim().is_empty() {
+                let key = format!("forbidden_folders[{}]", i);
+                return Err(invalid(&key, folder, "must not be empty"));
+            }
+        }
+
+        self.date_rules.clear();
+        for (i, rule) in self.forbidden_dates.iter().enumerate() {
+            match rule.parse::<DateRule>() {
+                Ok(parsed) => self.date_rules.push(parsed),
+                Err(e) => {
+                    let key = format!("forbidden_dates[{}]", i);
+                    return Err(invalid(&key, rule, &e));
+                }
+            }
+        }
+
+        if let Some(selector) = &self.pub_date_selector {
+            if Selector::parse(selector).is_err() {
+                return Err(invalid(
+                    "pub_date_selector",
+                    selector,
+                    "is not a valid CSS selector",
+                ));
+            }
+        }
+
+        match Regex::new(&self.tag_url_pattern) {
+            Err(e) => {
+                return Err(invalid(
+                    "tag_url_pattern",
+                    &self.tag_url_pattern,
+                    &e.to_string(),
+                ))
+            }
+            Ok(re) if re.captures_len() < 2 => {
+                return Err(invalid(
+                    "tag_url_pattern",
+                    &self.tag_url_pattern,
+                    "needs a capture group for the tag name",
+                ))
+            }
+            Ok(re) => self.tag_url_regex = re,
+        }
+
+        let external = &self.external_links;
+        for (name, patterns) in &[("allow", &external.allow), ("deny", &external.deny)] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(e) = Regex::new(pattern) {
+                    let key = format!("external_links.{}[{}]", name, i);
+                    return Err(invalid(&key, pattern, &e.to_string()));
+                }
+            }
+        }
+        if external.concurrency == 0 {
+            return Err(invalid(
+                "external_links.concurrency",
+                "0",
+                "must be at least 1",
+            ));
+        }
+
+        if let Some(dir) = &self.spelling.dictionaries {
+            if !dir.is_dir() {
+                let value = dir.display().to_string();
+                return Err(invalid("spelling.dictionaries", &value, "is not a folder"));
+            }
+        }
+        if let Some(file) = &self.spelling.word_list {
+            if !file.is_file() {
+                let value = file.display().to_string();
+                return Err(invalid("spelling.word_list", &value, "is not a file"));
+            }
+        }
+
+        let readability = &self.readability;
+        if readability.max_sentence_length <= 0.0 {
+            return Err(invalid(
+                "readability.max_sentence_length",
+                &readability.max_sentence_length.to_string(),
+                "must be more than 0",
+            ));
+        }
+        if !(0.0..=1.0).contains(&readability.max_passive_ratio) {
+            return Err(invalid(
+                "readability.max_passive_ratio",
+                &readability.max_passive_ratio.to_string(),
+                "must be between 0 and 1",
+            ));
+        }
+
+        let mut known_rules = rule_ids();
+        let mut style_ids = HashSet::new();
+        for (i, rule) in self.style_rules.iter_mut().enumerate() {
+            let key = |field: &str| format!("style_rules[{}].{}", i, field);
+            if rule.id.trim().is_empty() {
+                return Err(invalid(&key("id"), rule.id, "must not be empty"));
+            }
+            if known_rules.contains(&rule.id) {
+                return Err(invalid(&key("id"), rule.id, "is already a built-in rule"));
+            }
+            if !style_ids.insert(rule.id) {
+                return Err(invalid(
+                    &key("id"),
+                    rule.id,
+                    "is used by another style rule",
+                ));
+            }
+            match (&rule.pattern, rule.replace.is_empty()) {
+                (Some(_), true) | (None, false) => (),
+                _ => {
+                    return Err(invalid(
+                        &key("pattern"),
+                        rule.pattern.as_deref().unwrap_or(""),
+                        "exactly one of pattern and replace must be set",
+                    ))
+                }
+            }
+            match rule.compile_matcher() {
+                Ok(re) => rule.matcher = Some(re),
+                Err(e) => {
+                    let pattern = rule.pattern.as_deref().unwrap_or("");
+                    return Err(invalid(&key("pattern"), pattern, &e.to_string()));
+                }
+            }
+            if let Some(selector) = &rule.selector {
+                match Selector::parse(selector) {
+                    Ok(parsed) => rule.parsed_selector = Some(parsed),
+                    Err(_) => {
+                        return Err(invalid(
+                            &key("selector"),
+                            selector,
+                            "is not a valid CSS selector",
+                        ))
+                    }
+                }
+            }
+        }
+        known_rules.extend(style_ids);
+
+        for id in self.rules.keys() {
+            if !known_rules.contains(&id.as_str()) {
+                let key = format!("rules.{}", id);
+                return Err(invalid(&key, id, "is not a known rule, see --list-rules"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(key: &str, value: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+pub fn parse_config(contents: &str) -> Result<ReaderConfig, ConfigError> {
+    let mut conf: ReaderConfig = toml::from_str(contents)?;
+    conf.validate()?;
+    Ok(conf)
+}
+
+pub fn build_config(path: Option<&Path>) -> Result<ReaderConfig, ConfigError> {
+    match path {
+        None => Ok(ReaderConfig::default()),
+        Some(p) => {
+            let contents = read_to_string(p).map_err(|e| ConfigError::Io {
+                path: p.display().to_string(),
+                err: e,
+            })?;
+            parse_config(&contents)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_repo_config() {
+        let conf = parse_config(include_str!("../config.toml")).expect("config should parse");
+
+        assert_eq!(conf.forbidden_tags, vec!["WIP".to_string()]);
+        assert_eq!(conf.forbidden_folders.len(), 3);
+        assert_eq!(conf.forbidden_dates.len(), 3);
+        assert_eq!(conf.date_rules.len(), 3);
+        assert_eq!(conf.pub_date_selector, Some("div.tags".to_string()));
+        assert_eq!(conf.root_dir, PathBuf::from("./public"));
+        assert_eq!(conf.tag_url_pattern, DEFAULT_TAG_URL_PATTERN);
+    }
+
+    #[test]
+    fn test_missing_keys_use_defaults() {
+        let conf = parse_config("root_dir = \"site\"").expect("config should parse");
+
+        assert_eq!(conf.root_dir, PathBuf::from("site"));
+        assert_eq!(conf.forbidden_tags, ReaderConfig::default().forbidden_tags);
+        assert_eq!(conf.pub_date_selector, None);
+    }
+
+    #[test]
+    fn test_rejects_unknown_key() {
+        let res = parse_config("forbiden_tags = [\"WIP\"]");
+        assert!(matches!(res, Err(ConfigError::Parse(_))), "{:?}", res);
+    }
+
+    #[test]
+    fn test_invalid_date_rule_points_at_key() {
+        let res = parse_config("forbidden_dates = [\"0000-01-01\", \"<1950-13-01\"]");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, value, .. }) => {
+                assert_eq!(key, "forbidden_dates[1]");
+                assert_eq!(value, "<1950-13-01");
+            }
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_selector_points_at_key() {
+        let res = parse_config("pub_date_selector = \"div..tags\"");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "pub_date_selector"),
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tag_pattern_needs_capture_group() {
+        let res = parse_config("tag_url_pattern = \"tags/[a-z]+\"");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "tag_url_pattern"),
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+
+        let conf = parse_config("tag_url_pattern = \"topics/([a-z]+)\"").unwrap();
+        assert_eq!(conf.tag_url_regex.as_str(), "topics/([a-z]+)");
+    }
+
+    #[test]
+    fn test_parses_rule_levels() {
+        let conf = parse_config(
+            r#"
+            [rules]
+            img-alt = "off"
+            single-h1 = "warn"
+            no-title-attr = "error"
+            "#,
+        )
+        .expect("config should parse");
+
+        assert_eq!(conf.rule_severity("img-alt", Severity::Error), None);
+        assert_eq!(
+            conf.rule_severity("single-h1", Severity::Error),
+            Some(Severity::Warning)
+        );
+        assert_eq!(
+            conf.rule_severity("no-title-attr", Severity::Warning),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            conf.rule_severity("page-title", Severity::Error),
+            Some(Severity::Error)
+        );
+    }
+
+    #[test]
+    fn test_unknown_rule_points_at_key() {
+        let res = parse_config("[rules]\nimg-alts = \"off\"");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "rules.img-alts"),
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_rule_level() {
+        let res = parse_config("[rules]\nimg-alt = \"fatal\"");
+        assert!(matches!(res, Err(ConfigError::Parse(_))), "{:?}", res);
+    }
+
+    #[test]
+    fn test_invalid_glob_points_at_key() {
+        let res = parse_config("excluded_file_glob = \"**a/[\"");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "excluded_file_glob"),
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parses_globs() {
+        let conf = parse_config(
+            r#"
+            excluded_file_glob = "drafts"
+            include_globs = ["*.html"]
+            exclude_globs = ["static/*"]
+            "#,
+        )
+        .expect("config should parse");
+
+        assert_eq!(conf.include_globs, vec!["*.html".to_string()]);
+        assert_eq!(
+            conf.excludes(),
+            vec!["drafts".to_string(), "static/*".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_invalid_glob_list_points_at_key() {
+        let res = parse_config("exclude_globs = [\"drafts\", \"**a/[\"]");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "exclude_globs[1]"),
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parses_external_links_table() {
+        let conf = parse_config(
+            r#"
+            [external_links]
+            deny = ["^https://localhost"]
+            concurrency = 2
+            "#,
+        )
+        .expect("config should parse");
+
+        assert_eq!(conf.external_links.deny, vec!["^https://localhost"]);
+        assert_eq!(conf.external_links.concurrency, 2);
+        assert_eq!(conf.external_links.retries, 2);
+    }
+
+    #[test]
+    fn test_parses_generator() {
+        let conf = parse_config("generator = \"jekyll\"").expect("config should parse");
+        assert_eq!(conf.generator, Generator::Jekyll);
+
+        assert_eq!(ReaderConfig::default().generator, Generator::Zola);
+        assert!(parse_config("generator = \"gatsby\"").is_err());
+    }
+
+    #[test]
+    fn test_missing_word_list_is_invalid() {
+        let res = parse_config("[spelling]\nword_list = \"does-not-exist.txt\"");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, "spelling.word_list"),
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_style_rules_point_at_key() {
+        for (rule, expected_key) in &[
+            (
+                "id = \"img-alt\"\nmessage = \"m\"\npattern = \"x\"",
+                "style_rules[0].id",
+            ),
+            (
+                "id = \"a\"\nmessage = \"m\"\npattern = \"(\"",
+                "style_rules[0].pattern",
+            ),
+            ("id = \"a\"\nmessage = \"m\"", "style_rules[0].pattern"),
+            (
+                "id = \"a\"\nmessage = \"m\"\npattern = \"x\"\nselector = \"[\"",
+                "style_rules[0].selector",
+            ),
+        ] {
+            match parse_config(&format!("[[style_rules]]\n{}", rule)) {
+                Err(ConfigError::InvalidValue { key, .. }) => assert_eq!(key, *expected_key),
+                other => panic!("expected invalid value error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_style_rules_can_be_configured() {
+        let conf = parse_config(
+            "[rules]\nhouse-style = \"error\"\n\n[[style_rules]]\nid = \"house-style\"\nmessage = \"Not our spelling\"\nreplace = { \"e-mail\" = \"email\" }",
+        )
+        .unwrap();
+
+        assert_eq!(conf.style_rules[0].id, "house-style");
+        assert_eq!(conf.style_rules[0].severity, RuleLevel::Warn);
+        assert_eq!(
+            conf.rule_severity("house-style", Severity::Warning),
+            Some(Severity::Error)
+        );
+        let matcher = conf.style_rules[0].matcher.as_ref();
+        assert_eq!(matcher.map(Regex::as_str), Some(r"\b(?:e\-mail)\b"));
+    }
+
+    #[test]
+    fn test_passive_ratio_is_a_fraction() {
+        let res = parse_config("[readability]\nmax_passive_ratio = 25.0");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => {
+                assert_eq!(key, "readability.max_passive_ratio")
+            }
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_url_pattern_points_at_key() {
+        let res = parse_config("[external_links]\nallow = [\"https://(\"]");
+
+        match res {
+            Err(ConfigError::InvalidValue { key, .. }) => {
+                assert_eq!(key, "external_links.allow[0]")
+            }
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+}
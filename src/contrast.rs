@@ -0,0 +1,675 @@
+use lazy_static::*;
+use palette::{named, Hsl, IntoColor, Srgb};
+use regex::Regex;
+use scraper::{ElementRef, Selector};
+use std::collections::HashSet;
+use std::fs::read_to_string;
+
+use crate::css::{parse_declarations, Declaration, Stylesheet};
+use crate::html::Page;
+
+/// Font size of the root element when nothing says otherwise.
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// WCAG 2.1 minimum contrast ratios, for normal and for large text.
+pub const MIN_RATIO: f32 = 4.5;
+pub const MIN_RATIO_LARGE_TEXT: f32 = 3.0;
+
+/// An sRGB color with its alpha, all components between 0 and 1.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Color {
+    pub rgb: Srgb,
+    pub alpha: f32,
+}
+
+impl Color {
+    fn opaque(r: f32, g: f32, b: f32) -> Color {
+        Color {
+            rgb: Srgb::new(r, g, b),
+            alpha: 1.0,
+        }
+    }
+
+    /// This color painted on top of `backdrop`, blended the way browsers do.
+    fn over(self, backdrop: Color) -> Color {
+        let blend = |top: f32, bottom: f32| top * self.alpha + bottom * (1.0 - self.alpha);
+        Color::opaque(
+            blend(self.rgb.red, backdrop.rgb.red),
+            blend(self.rgb.green, backdrop.rgb.green),
+            blend(self.rgb.blue, backdrop.rgb.blue),
+        )
+    }
+
+    fn relative_luminance(self) -> f32 {
+        self.rgb.into_linear().into_xyz().y
+    }
+
+    pub fn to_hex(self) -> String {
+        let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            byte(self.rgb.red),
+            byte(self.rgb.green),
+            byte(self.rgb.blue)
+        )
+    }
+}
+
+/// The WCAG contrast ratio between two opaque colors, from 1 to 21.
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let (a, b) = (a.relative_luminance(), b.relative_luminance());
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Parses a CSS color: a hex color, `rgb()`, `rgba()`, `hsl()`, `hsla()`,
+/// a named color or `transparent`.
+pub fn parse_color(value: &str) -> Option<Color> {
+    lazy_static! {
+        static ref RE_HEX: Regex =
+            Regex::new(r"^#([0-9a-fA-F]{3,4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$").unwrap();
+        static ref RE_FUNCTION: Regex = Regex::new(r"^(?i)(rgba?|hsla?)\(([^)]*)\)$").unwrap();
+    }
+    let value = value.trim().to_ascii_lowercase();
+
+    if let Some(caps) = RE_HEX.captures(&value) {
+        let hex = &caps[1];
+        let digits = match hex.len() {
+            3 | 4 => hex
+                .chars()
+                .map(|c| format!("{}{}", c, c))
+                .collect::<String>(),
+            _ => hex.to_string(),
+        };
+        let channel = |i: usize| {
+            u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .ok()
+                .map(|c| f32::from(c) / 255.0)
+        };
+        return Some(Color {
+            rgb: Srgb::new(channel(0)?, channel(1)?, channel(2)?),
+            alpha: if digits.len() == 8 { channel(3)? } else { 1.0 },
+        });
+    }
+
+    if let Some(caps) = RE_FUNCTION.captures(&value) {
+        let args = caps[2]
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|a| !a.is_empty())
+            .collect::<Vec<_>>();
+        if args.len() < 3 || args.len() > 4 {
+            return None;
+        }
+        let alpha = match args.get(3) {
+            Some(a) => parse_fraction(a, 1.0)?,
+            None => 1.0,
+        };
+        let rgb = if caps[1].starts_with("rgb") {
+            Srgb::new(
+                parse_fraction(args[0], 255.0)?,
+                parse_fraction(args[1], 255.0)?,
+                parse_fraction(args[2], 255.0)?,
+            )
+        } else {
+            let hue = args[0].trim_end_matches("deg").parse::<f32>().ok()?;
+            let hsl: Hsl = Hsl::new(
+                hue,
+                parse_fraction(args[1], 1.0)?,
+                parse_fraction(args[2], 1.0)?,
+            );
+            Srgb::from(hsl)
+        };
+        return Some(Color { rgb, alpha });
+    }
+
+    match value.as_str() {
+        "transparent" => Some(Color {
+            rgb: Srgb::new(0.0, 0.0, 0.0),
+            alpha: 0.0,
+        }),
+        name => named::from_str(name).map(|c| Color {
+            rgb: Srgb::new(
+                f32::from(c.red) / 255.0,
+                f32::from(c.green) / 255.0,
+                f32::from(c.blue) / 255.0,
+            ),
+            alpha: 1.0,
+        }),
+    }
+}
+
+/// Parses a number or a percentage into a fraction of `scale`, clamped to 0..=1.
+fn parse_fraction(s: &str, scale: f32) -> Option<f32> {
+    let fraction = match s.strip_suffix('%') {
+        Some(p) => p.parse::<f32>().ok()? / 100.0,
+        None => s.parse::<f32>().ok()? / scale,
+    };
+    Some(fraction.clamp(0.0, 1.0))
+}
+
+/// Properties the contrast check needs, anything else in a stylesheet is ignored.
+const PROPERTIES: &[&str] = &[
+    "color",
+    "background-color",
+    "background",
+    "font-size",
+    "font-weight",
+];
+
+/// Where a declaration came from, in increasing order of precedence
+/// for declarations that are equally important.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+struct Precedence {
+    important: bool,
+    inline: bool,
+    specificity: u32,
+    order: usize,
+}
+
+struct StyleRule {
+    selector: Selector,
+    specificity: u32,
+    declarations: Vec<Declaration>,
+}
+
+/// The style rules that apply to a page, from its `<style>` elements and
+/// the stylesheets it links to, in the order they appear.
+fn collect_style_rules(page: &Page) -> Vec<StyleRule> {
+    let styles_selector = Selector::parse("style, link[rel~=stylesheet][href]").unwrap();
+
+    let mut sheets = vec![];
+    for elem in page.document.select(&styles_selector) {
+        if elem.value().name() == "style" {
+            sheets.push(elem.text().collect::<String>());
+            continue;
+        }
+        // only stylesheets from the site itself can be read
+        let href = elem.value().attr("href").unwrap_or_default();
+        let target = page.site.and_then(|site| site.resolve(page.path, href));
+        if let Some(contents) = target.and_then(|link| read_to_string(link.target).ok()) {
+            sheets.push(contents);
+        }
+    }
+
+    let mut rules = vec![];
+    for source in &sheets {
+        let sheet = Stylesheet::parse(page.path, source);
+        for rule in sheet.rules {
+            let declarations = rule
+                .declarations
+                .into_iter()
+                .filter(|d| PROPERTIES.contains(&d.property.as_str()))
+                .collect::<Vec<_>>();
+            if declarations.is_empty() {
+                continue;
+            }
+            // selectors in a list can have different specificities, so they're split up.
+            // Anything the selector parser doesn't understand can't be matched anyway.
+            for selector in rule.selector.split(',') {
+                if let Ok(selector) = Selector::parse(selector) {
+                    let specificity = selector.selectors.iter().map(|s| s.specificity()).max();
+                    rules.push(StyleRule {
+                        selector,
+                        specificity: specificity.unwrap_or(0),
+                        declarations: declarations.clone(),
+                    });
+                }
+            }
+        }
+    }
+    rules
+}
+
+/// The winning value of every property the check needs, for a single element.
+#[derive(Debug, Default)]
+struct Cascaded {
+    color: Option<String>,
+    background: Option<String>,
+    font_size: Option<String>,
+    font_weight: Option<String>,
+}
+
+fn cascade(elem: ElementRef, rules: &[StyleRule]) -> Cascaded {
+    let mut declarations = vec![];
+    for (order, rule) in rules.iter().enumerate() {
+        if rule.selector.matches(&elem) {
+            for d in &rule.declarations {
+                let precedence = Precedence {
+                    important: d.important,
+                    inline: false,
+                    specificity: rule.specificity,
+                    order,
+                };
+                declarations.push((precedence, d.property.clone(), d.value.clone()));
+            }
+        }
+    }
+    if let Some(style) = elem.value().attr("style") {
+        for d in parse_declarations(style, 0) {
+            if PROPERTIES.contains(&d.property.as_str()) {
+                let precedence = Precedence {
+                    important: d.important,
+                    inline: true,
+                    specificity: 0,
+                    order: 0,
+                };
+                declarations.push((precedence, d.property, d.value));
+            }
+        }
+    }
+    // stable, so later declarations of the same rule still win
+    declarations.sort_by_key(|(precedence, _, _)| *precedence);
+
+    let mut cascaded = Cascaded::default();
+    for (_, property, value) in declarations {
+        match property.as_str() {
+            "color" => cascaded.color = Some(value),
+            "background-color" | "background" => cascaded.background = Some(value),
+            "font-size" => cascaded.font_size = Some(value),
+            "font-weight" => cascaded.font_weight = Some(value),
+            _ => (),
+        }
+    }
+    cascaded
+}
+
+/// The styles of an element that matter for its contrast.
+#[derive(Debug, Clone, Copy)]
+struct Computed {
+    /// `None` if it can't be known, like for `var(--fg)`.
+    color: Option<Color>,
+    /// What's painted behind the element, `None` if that's an image, a gradient
+    /// or a color that can't be known.
+    background: Option<Color>,
+    font_size: f32,
+    bold: bool,
+}
+
+/// Font size multipliers browsers give headings by default.
+fn default_font_scale(name: &str) -> f32 {
+    match name {
+        "h1" => 2.0,
+        "h2" => 1.5,
+        "h3" => 1.17,
+        "h5" => 0.83,
+        "h6" => 0.67,
+        "small" => 0.83,
+        _ => 1.0,
+    }
+}
+
+fn bold_by_default(name: &str) -> bool {
+    matches!(
+        name,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "b" | "strong" | "th"
+    )
+}
+
+fn parse_font_size(value: &str, parent: f32, root: f32) -> Option<f32> {
+    lazy_static! {
+        static ref RE_LENGTH: Regex = Regex::new(r"^(\d*\.?\d+)(px|pt|em|rem|%)$").unwrap();
+    }
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(caps) = RE_LENGTH.captures(&value) {
+        let n = caps[1].parse::<f32>().ok()?;
+        return Some(match &caps[2] {
+            "px" => n,
+            "pt" => n * 4.0 / 3.0,
+            "em" => n * parent,
+            "rem" => n * root,
+            _ => n * parent / 100.0,
+        });
+    }
+    Some(match value.as_str() {
+        "xx-small" => 9.0,
+        "x-small" => 10.0,
+        "small" => 13.0,
+        "medium" => 16.0,
+        "large" => 18.0,
+        "x-large" => 24.0,
+        "xx-large" => 32.0,
+        "smaller" => parent / 1.2,
+        "larger" => parent * 1.2,
+        _ => return None,
+    })
+}
+
+fn parse_bold(value: &str, parent: bool) -> bool {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "bold" | "bolder" => true,
+        "normal" | "lighter" => false,
+        n => n.parse::<u32>().map_or(parent, |weight| weight >= 700),
+    }
+}
+
+/// Splits a shorthand value on whitespace, keeping functions like `rgb(1, 2, 3)` whole.
+fn split_values(value: &str) -> Vec<&str> {
+    let mut values = vec![];
+    let mut depth = 0;
+    let mut start = None;
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(s) = start.take() {
+                    values.push(&value[s..i]);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        values.push(&value[s..]);
+    }
+    values
+}
+
+/// Resolves a `background` or `background-color` value against what's behind it.
+fn resolve_background(value: &str, color: Option<Color>, behind: Option<Color>) -> Option<Color> {
+    let value = value.trim().to_ascii_lowercase();
+    if value.contains("url(") || value.contains("gradient(") {
+        return None;
+    }
+    let mut painted = None;
+    for token in split_values(&value) {
+        painted = match token {
+            "currentcolor" => Some(color?),
+            // a function that isn't a color, like `var(--bg)`, could be any color
+            token if token.contains('(') => Some(parse_color(token)?),
+            token => parse_color(token),
+        };
+        if painted.is_some() {
+            break;
+        }
+    }
+    match (painted, behind) {
+        (Some(painted), Some(behind)) => Some(painted.over(behind)),
+        // the shorthand without any color, like `background: none`
+        (None, behind) => behind,
+        (Some(_), None) => None,
+    }
+}
+
+fn compute(
+    elem: ElementRef,
+    parent: &Computed,
+    root_font_size: f32,
+    rules: &[StyleRule],
+) -> Computed {
+    let cascaded = cascade(elem, rules);
+    let name = elem.value().name();
+
+    let color = match cascaded.color.as_deref().map(str::trim) {
+        Some(v) if v.eq_ignore_ascii_case("currentcolor") || v.eq_ignore_ascii_case("inherit") => {
+            parent.color
+        }
+        // like `var(--fg)`, which could be any color
+        Some(v) => parse_color(v),
+        None => parent.color,
+    };
+    let background = match &cascaded.background {
+        Some(v) => resolve_background(v, color, parent.background),
+        None => parent.background,
+    };
+    let default_size = parent.font_size * default_font_scale(name);
+    let font_size = cascaded
+        .font_size
+        .and_then(|v| parse_font_size(&v, parent.font_size, root_font_size))
+        .unwrap_or(default_size);
+    let bold = match &cascaded.font_weight {
+        Some(v) => parse_bold(v, parent.bold),
+        None => parent.bold || bold_by_default(name),
+    };
+
+    Computed {
+        color,
+        background,
+        font_size,
+        bold,
+    }
+}
+
+/// Text with too little contrast against its background.
+pub struct LowContrast<'a> {
+    pub element: ElementRef<'a>,
+    pub color: Color,
+    pub background: Color,
+    pub ratio: f32,
+    pub minimum: f32,
+}
+
+/// Elements whose text isn't shown as regular text on the page.
+const NON_TEXT_ELEMENTS: &[&str] = &[
+    "head", "title", "script", "style", "noscript", "template", "svg", "math", "textarea",
+    "select", "option",
+];
+
+fn has_own_text(elem: ElementRef) -> bool {
+    elem.children()
+        .filter_map(|child| child.value().as_text())
+        .any(|text| !text.trim().is_empty())
+}
+
+/// Finds every element with text of its own whose colors contrast too little.
+/// Elements on top of images or gradients, or with colors that can't be parsed like
+/// `var(--fg)`, are skipped, their contrast can't be known.
+/// Only the first element with text is reported of those that keep the colors of
+/// the same ancestor, so a single low contrast color gives a single finding.
+pub fn find_low_contrast<'a>(page: &'a Page) -> Vec<LowContrast<'a>> {
+    let rules = collect_style_rules(page);
+    let canvas = Computed {
+        color: Some(Color::opaque(0.0, 0.0, 0.0)),
+        background: Some(Color::opaque(1.0, 1.0, 1.0)),
+        font_size: DEFAULT_FONT_SIZE,
+        bold: false,
+    };
+    let root = page.document.root_element();
+    let root_style = compute(root, &canvas, DEFAULT_FONT_SIZE, &rules);
+
+    let mut found = vec![];
+    // ancestors whose low contrast colors were already reported on an element
+    let mut reported = HashSet::new();
+    // along with every element, the outermost ancestor with the same low contrast colors
+    let mut stack = vec![(root, root_style, None)];
+    while let Some((elem, style, inherited)) = stack.pop() {
+        if NON_TEXT_ELEMENTS.contains(&elem.value().name()) {
+            continue;
+        }
+        let mut origin = None;
+        if let (Some(color), Some(background)) = (style.color, style.background) {
+            let color = color.over(background);
+            let ratio = contrast_ratio(color, background);
+            let large = style.font_size >= 24.0 || (style.bold && style.font_size >= 18.66);
+            let minimum = if large {
+                MIN_RATIO_LARGE_TEXT
+            } else {
+                MIN_RATIO
+            };
+            if ratio < minimum {
+                let colors = (color, background, minimum);
+                let origin_id = match inherited {
+                    Some((id, inherited_colors)) if inherited_colors == colors => id,
+                    _ => elem.id(),
+                };
+                origin = Some((origin_id, colors));
+                if has_own_text(elem) && reported.insert(origin_id) {
+                    found.push(LowContrast {
+                        element: elem,
+                        color,
+                        background,
+                        ratio,
+                        minimum,
+                    });
+                }
+            }
+        }
+        // pushed in reverse so they're visited in document order
+        let children = elem
+            .children()
+            .filter_map(ElementRef::wrap)
+            .collect::<Vec<_>>();
+        for child in children.into_iter().rev() {
+            let child_style = compute(child, &style, root_style.font_size, &rules);
+            stack.push((child, child_style, origin));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn hex(value: &str) -> Option<String> {
+        parse_color(value).map(|c| c.to_hex())
+    }
+
+    #[test]
+    fn test_parses_colors() {
+        assert_eq!(hex("#FfF"), Some("#ffffff".to_string()));
+        assert_eq!(hex("#336699"), Some("#336699".to_string()));
+        assert_eq!(hex("rgb(255, 0, 0)"), Some("#ff0000".to_string()));
+        assert_eq!(hex("rgb(0 128 0 / 50%)"), Some("#008000".to_string()));
+        assert_eq!(hex("hsl(240, 100%, 50%)"), Some("#0000ff".to_string()));
+        assert_eq!(hex("RebeccaPurple"), Some("#663399".to_string()));
+        assert_eq!(parse_color("#12345").map(|c| c.alpha), None);
+        assert_eq!(
+            parse_color("rgba(0, 0, 0, 0.25)").map(|c| c.alpha),
+            Some(0.25)
+        );
+        assert_eq!(parse_color("transparent").map(|c| c.alpha), Some(0.0));
+        assert_eq!(parse_color("not-a-color"), None);
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let black = Color::opaque(0.0, 0.0, 0.0);
+        let white = Color::opaque(1.0, 1.0, 1.0);
+
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(white, white) - 1.0).abs() < 0.01);
+        // #777 on white is the classic just-not-enough grey
+        let grey = parse_color("#777").unwrap();
+        assert!((contrast_ratio(grey, white) - 4.48).abs() < 0.01);
+    }
+
+    fn low_contrast(source: &str) -> Vec<(String, String, String)> {
+        let page = Page::parse(Path::new("page.html"), source);
+        find_low_contrast(&page)
+            .iter()
+            .map(|l| {
+                (
+                    l.element.value().name().to_string(),
+                    format!("{:.2}", l.ratio),
+                    l.minimum.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolves_inline_and_stylesheet_rules() {
+        let found = low_contrast(
+            r#"<html><head><style>
+            p { color: #999 }
+            .dark { background-color: #222 }
+            .dark p, p.light { color: white }
+            #hint { color: #444 !important }
+            </style></head><body>
+            <p>grey on white</p>
+            <p class="light">white on white</p>
+            <div class="dark"><p>white on dark</p><p id="hint" style="color: white">important wins</p></div>
+            <p style="color: #595959">dark enough</p>
+            </body></html>"#,
+        );
+
+        assert_eq!(
+            found,
+            vec![
+                ("p".to_string(), "2.85".to_string(), "4.5".to_string()),
+                ("p".to_string(), "1.00".to_string(), "4.5".to_string()),
+                ("p".to_string(), "1.63".to_string(), "4.5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_inherited_colors_once() {
+        let found = low_contrast(
+            r#"<html><head><style>body { color: #777 }</style></head><body>
+            <p>Grey text with <a href="/">a <em>grey</em> link</a> and <em>stress</em>.</p>
+            <p>Another <strong style="color: #aaa">lighter</strong> one.</p>
+            </body></html>"#,
+        );
+
+        assert_eq!(
+            found,
+            vec![
+                ("p".to_string(), "4.48".to_string(), "4.5".to_string()),
+                ("strong".to_string(), "2.32".to_string(), "4.5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_text_needs_less_contrast() {
+        let found = low_contrast(
+            r#"<html><body style="color: #949494">
+            <h1>large</h1>
+            <h4>bold, but not large enough</h4>
+            <p style="font-size: 1.5em">large</p>
+            <p style="font-size: 14pt; font-weight: 700">large and bold</p>
+            <p>small</p>
+            </body></html>"#,
+        );
+
+        // the small p fails the same way with the body's colors, which the h4 already reported
+        assert_eq!(
+            found.iter().map(|f| f.0.as_str()).collect::<Vec<_>>(),
+            vec!["h4"]
+        );
+        assert_eq!(found[0].2, "4.5");
+    }
+
+    #[test]
+    fn test_composites_transparent_colors() {
+        let found = low_contrast(
+            r#"<html><body style="background: #000">
+            <div style="background-color: rgba(255, 255, 255, 0.5)">
+              <p style="color: rgba(255, 255, 255, 0.5)">faded</p>
+            </div>
+            <p style="color: white">fine</p>
+            <div style="background: url(photo.jpg) #fff"><p style="color: white">unknown</p></div>
+            </body></html>"#,
+        );
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "p");
+    }
+
+    #[test]
+    fn test_skips_colors_that_cant_be_known() {
+        let found = low_contrast(
+            r#"<html><head><style>
+            body { background: #222; color: var(--fg) }
+            .card { background-color: var(--bg, #fff); color: #eee }
+            .known { color: #333 }
+            </style></head><body>
+            <h1>Title</h1>
+            <p>Themed text</p>
+            <div class="card"><p>on an unknown background</p></div>
+            <p class="known">dark on dark</p>
+            </body></html>"#,
+        );
+
+        assert_eq!(
+            found.iter().map(|f| f.0.as_str()).collect::<Vec<_>>(),
+            vec!["p"]
+        );
+        // only the known color, #333 on #222
+        assert_eq!(found[0].1, "1.26");
+    }
+}
@@ -0,0 +1,668 @@
+use lazy_static::*;
+use regex::Regex;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::check::{run_checks, Check, CheckResult};
+use crate::error::{Category, CheckError, Severity};
+use crate::location::{Location, SourceMap};
+use crate::site::Site;
+use crate::ReaderConfig;
+
+/// A rule run against every stylesheet, see `check::Check`.
+pub type CssCheck = dyn for<'a> Check<Stylesheet<'a>>;
+
+// Declares a unit struct implementing `Check` for one of the check functions below.
+macro_rules! css_check {
+    ($($rule:tt)*) => {
+        check!(Stylesheet, $($rule)*);
+    };
+}
+
+css_check!(
+    FocusOutline,
+    "css-focus-outline",
+    "Focus styles don't remove the outline",
+    Accessibility,
+    Error,
+    |sheet, _conf| check_focus_keeps_outline(sheet)
+);
+css_check!(
+    ImportantOveruse,
+    "css-important",
+    "Stylesheet doesn't overuse !important",
+    Content,
+    Warning,
+    |sheet, conf| check_important_overuse(sheet, conf.max_important)
+);
+css_check!(
+    PxFontSize,
+    "css-px-font-size",
+    "Font sizes scale with the user's settings",
+    Accessibility,
+    Warning,
+    |sheet, _conf| check_font_sizes_not_only_px(sheet)
+);
+css_check!(
+    MissingAsset,
+    "css-missing-asset",
+    "url() references point to existing files",
+    Content,
+    Error,
+    |sheet, _conf| check_urls_exist(sheet)
+);
+css_check!(
+    UserSelect,
+    "css-user-select",
+    "Body text can be selected",
+    Accessibility,
+    Warning,
+    |sheet, _conf| check_text_is_selectable(sheet)
+);
+
+static RULES: &[&CssCheck] = &[
+    &FocusOutline,
+    &ImportantOveruse,
+    &PxFontSize,
+    &MissingAsset,
+    &UserSelect,
+];
+
+/// All css rules, in the order they are run.
+pub fn rules() -> &'static [&'static CssCheck] {
+    RULES
+}
+
+/// A single `property: value` pair.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Declaration {
+    /// Lowercased property name.
+    pub property: String,
+    /// The value, without any `!important`.
+    pub value: String,
+    pub important: bool,
+    /// Byte offset of the start of the property name.
+    pub offset: usize,
+}
+
+/// A selector and the declarations in its block.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StyleRule {
+    pub selector: String,
+    pub declarations: Vec<Declaration>,
+    /// Byte offset of the start of the selector.
+    pub offset: usize,
+}
+
+/// A parsed CSS file. Only plain style rules are kept, including those nested in
+/// conditional at-rules like `@media`, anything else is skipped.
+pub struct Stylesheet<'a> {
+    pub path: &'a Path,
+    pub source: &'a str,
+    pub rules: Vec<StyleRule>,
+    /// The site the stylesheet is part of, used to resolve `url()`s.
+    pub site: Option<&'a Site>,
+    source_map: SourceMap,
+}
+
+impl<'a> Stylesheet<'a> {
+    pub fn parse(path: &'a Path, source: &'a str) -> Stylesheet<'a> {
+        Stylesheet {
+            path,
+            source,
+            rules: parse_rules(source),
+            site: None,
+            source_map: SourceMap::for_text(source),
+        }
+    }
+
+    pub fn location_of_offset(&self, offset: usize) -> Location {
+        self.source_map.location_of_offset(self.source, offset)
+    }
+
+    fn finding(
+        &self,
+        offender: String,
+        description: &str,
+        offset: usize,
+        rule: &'static str,
+        category: Category,
+    ) -> CheckError {
+        let (path, description, location) = (
+            self.path.display().to_string(),
+            description.to_string(),
+            Some(self.location_of_offset(offset)),
+        );
+        match category {
+            Category::Accessibility => CheckError::AccessibilityError {
+                path,
+                offender,
+                description,
+                location,
+                rule,
+                severity: Severity::Error,
+            },
+            Category::Content => CheckError::ContentError {
+                path,
+                offender,
+                description,
+                location,
+                rule,
+                severity: Severity::Error,
+            },
+        }
+    }
+}
+
+/// Blanks out comments, keeping every other byte where it was so offsets still line up.
+fn strip_comments(source: &str) -> String {
+    lazy_static! {
+        static ref RE_COMMENT: Regex = Regex::new(r"(?s)/\*.*?(\*/|$)").unwrap();
+    }
+    RE_COMMENT
+        .replace_all(source, |caps: &regex::Captures| {
+            caps[0]
+                .chars()
+                .map(|c| match c {
+                    '\n' => "\n".to_string(),
+                    c => " ".repeat(c.len_utf8()),
+                })
+                .collect::<String>()
+        })
+        .into_owned()
+}
+
+/// Byte offset of the first `wanted` char in `text` at nesting depth 0,
+/// not counting anything inside strings or parentheses.
+fn find_top_level(text: &str, wanted: &[char]) -> Option<usize> {
+    let mut quote = None;
+    let mut parens = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (_, _) if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => parens += 1,
+            (None, ')') => parens -= 1,
+            (None, c) if parens == 0 && wanted.contains(&c) => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Offset of the `}` closing the block whose contents start at the beginning of `text`.
+fn block_end(text: &str) -> usize {
+    let mut depth = 0;
+    let mut pos = 0;
+    while let Some(found) = find_top_level(&text[pos..], &['{', '}']) {
+        let i = pos + found;
+        if &text[i..=i] == "{" {
+            depth += 1;
+        } else if depth == 0 {
+            return i;
+        } else {
+            depth -= 1;
+        }
+        pos = i + 1;
+    }
+    text.len()
+}
+
+fn parse_rules(source: &str) -> Vec<StyleRule> {
+    let stripped = strip_comments(source);
+    let mut rules = vec![];
+    parse_block(&stripped, 0, &mut rules);
+    rules
+}
+
+/// At-rules whose block holds regular style rules.
+const CONDITIONAL_AT_RULES: &[&str] = &["@media", "@supports", "@container", "@layer", "@document"];
+
+fn parse_block(text: &str, base: usize, rules: &mut Vec<StyleRule>) {
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let end = match find_top_level(rest, &['{', ';', '}']) {
+            Some(e) => e,
+            None => break,
+        };
+        let prelude = rest[..end].trim();
+        let prelude_offset = base + pos + (rest.len() - rest.trim_start().len());
+
+        if &rest[end..=end] != "{" {
+            // a statement like `@import` or `@charset`, or a stray `}`
+            pos += end + 1;
+            continue;
+        }
+
+        let body_start = pos + end + 1;
+        let body_len = block_end(&text[body_start..]);
+        let body = &text[body_start..body_start + body_len];
+
+        if prelude.starts_with('@') {
+            let keyword = prelude
+                .split(|c: char| c.is_whitespace() || c == '(')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if CONDITIONAL_AT_RULES.contains(&keyword.as_str()) {
+                parse_block(body, base + body_start, rules);
+            }
+        } else {
+            rules.push(StyleRule {
+                selector: prelude.split_whitespace().collect::<Vec<_>>().join(" "),
+                declarations: parse_declarations(body, base + body_start),
+                offset: prelude_offset,
+            });
+        }
+        pos = body_start + body_len + 1;
+    }
+}
+
+/// Parses the `property: value` pairs of a block, `offset` is where the block starts.
+pub fn parse_declarations(body: &str, offset: usize) -> Vec<Declaration> {
+    lazy_static! {
+        static ref RE_IMPORTANT: Regex = Regex::new(r"(?i)!\s*important\s*$").unwrap();
+    }
+
+    let mut declarations = vec![];
+    let mut pos = 0;
+    while pos < body.len() {
+        let rest = &body[pos..];
+        let end = find_top_level(rest, &[';']).unwrap_or(rest.len());
+        let declaration = &rest[..end];
+
+        if let Some((property, value)) = declaration.split_once(':') {
+            let value = value.trim();
+            let important = RE_IMPORTANT.is_match(value);
+            let value = RE_IMPORTANT.replace(value, "").trim().to_string();
+            let leading_space = declaration.len() - declaration.trim_start().len();
+            if !property.trim().is_empty() {
+                declarations.push(Declaration {
+                    property: property.trim().to_ascii_lowercase(),
+                    value,
+                    important,
+                    offset: offset + pos + leading_space,
+                });
+            }
+        }
+        pos += end + 1;
+    }
+    declarations
+}
+
+pub fn check_css_file(path: &Path, conf: &ReaderConfig, site: Option<&Site>) -> CheckResult {
+    let contents = match read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return vec![CheckError::io(path, e)],
+    };
+    let mut sheet = Stylesheet::parse(path, &contents);
+    sheet.site = site;
+
+    run_checks(rules(), &sheet, conf).0
+}
+
+fn offender(rule: &StyleRule, declaration: &Declaration) -> String {
+    format!(
+        "{} {{ {}: {}{} }}",
+        rule.selector,
+        declaration.property,
+        declaration.value,
+        if declaration.important {
+            " !important"
+        } else {
+            ""
+        }
+    )
+}
+
+fn check_focus_keeps_outline(sheet: &Stylesheet) -> CheckResult {
+    lazy_static! {
+        // not `:focus-within`, which is about the container rather than what has focus
+        static ref RE_FOCUS: Regex = Regex::new(r"(?i):focus(-visible)?(?:[^\w-]|$)").unwrap();
+        static ref RE_NO_OUTLINE: Regex = Regex::new(r"(?i)^(none|0[a-z]*)$").unwrap();
+    }
+
+    sheet
+        .rules
+        .iter()
+        .filter(|rule| RE_FOCUS.is_match(&rule.selector))
+        .flat_map(|rule| {
+            rule.declarations
+                .iter()
+                .filter(|d| {
+                    ["outline", "outline-style", "outline-width"].contains(&d.property.as_str())
+                        && RE_NO_OUTLINE.is_match(&d.value)
+                })
+                .map(move |d| {
+                    sheet.finding(
+                        offender(rule, d),
+                        "Focus style removes the outline",
+                        d.offset,
+                        FocusOutline::ID,
+                        Category::Accessibility,
+                    )
+                })
+        })
+        .collect()
+}
+
+fn check_important_overuse(sheet: &Stylesheet, max_important: usize) -> CheckResult {
+    let important = sheet
+        .rules
+        .iter()
+        .flat_map(|rule| rule.declarations.iter().map(move |d| (rule, d)))
+        .filter(|(_, d)| d.important)
+        .collect::<Vec<_>>();
+
+    match important.get(max_important) {
+        // pointing at the first one over the limit, there's no telling which ones are needed
+        Some((rule, d)) => vec![sheet.finding(
+            offender(rule, d),
+            &format!(
+                "Stylesheet uses !important {} times, more than the {} allowed",
+                important.len(),
+                max_important
+            ),
+            d.offset,
+            ImportantOveruse::ID,
+            Category::Content,
+        )],
+        None => vec![],
+    }
+}
+
+fn check_font_sizes_not_only_px(sheet: &Stylesheet) -> CheckResult {
+    lazy_static! {
+        static ref RE_PX: Regex = Regex::new(r"(?i)\d(\.\d+)?px\b").unwrap();
+        static ref RE_RELATIVE: Regex =
+            Regex::new(r"(?i)\d(\.\d+)?(r?em|%|ex|ch|vw|vh|vmin|vmax)\b|\b(small|medium|large|smaller|larger|x+-small|x+-large|inherit|initial|unset|var)\b").unwrap();
+    }
+
+    sheet
+        .rules
+        .iter()
+        .flat_map(|rule| {
+            rule.declarations
+                .iter()
+                .filter(|d| {
+                    (d.property == "font-size" || d.property == "font")
+                        && RE_PX.is_match(&d.value)
+                        && !RE_RELATIVE.is_match(&d.value)
+                })
+                .map(move |d| {
+                    sheet.finding(
+                        offender(rule, d),
+                        "Font size is only given in px, so it doesn't scale with the user's settings",
+                        d.offset,
+                        PxFontSize::ID,
+                        Category::Accessibility,
+                    )
+                })
+        })
+        .collect()
+}
+
+fn check_urls_exist(sheet: &Stylesheet) -> CheckResult {
+    lazy_static! {
+        static ref RE_URL: Regex = Regex::new(
+            r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)|@import\s+(?:"([^"]*)"|'([^']*)')"#
+        )
+        .unwrap();
+    }
+    let site = match sheet.site {
+        Some(s) => s,
+        None => return vec![],
+    };
+
+    let stripped = strip_comments(sheet.source);
+    RE_URL
+        .captures_iter(&stripped)
+        .filter_map(|caps| {
+            let url = caps.iter().skip(1).flatten().next()?;
+            let link = site.resolve(sheet.path, url.as_str())?;
+            // fragments in stylesheets point into SVGs, not at pages
+            if link.target.is_file() || (link.target == sheet.path && link.fragment.is_some()) {
+                return None;
+            }
+            Some(sheet.finding(
+                url.as_str().to_string(),
+                "Stylesheet references a file that doesn't exist",
+                caps.get(0).unwrap().start(),
+                MissingAsset::ID,
+                Category::Content,
+            ))
+        })
+        .collect()
+}
+
+/// Elements that usually hold the running text of a page.
+const TEXT_ELEMENTS: &[&str] = &[
+    "*",
+    "html",
+    "body",
+    "main",
+    "article",
+    "section",
+    "p",
+    "li",
+    "blockquote",
+    "span",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+/// Whether one of the selectors in a list ends in an element that holds running text.
+fn targets_text(selector: &str) -> bool {
+    selector.split(',').any(|complex| {
+        let last = complex
+            .rsplit(|c: char| c.is_whitespace() || c == '>' || c == '+' || c == '~')
+            .find(|s| !s.is_empty())
+            .unwrap_or_default();
+        let element = last
+            .split(['.', '#', ':', '['])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        TEXT_ELEMENTS.contains(&element.as_str())
+    })
+}
+
+fn check_text_is_selectable(sheet: &Stylesheet) -> CheckResult {
+    sheet
+        .rules
+        .iter()
+        .filter(|rule| targets_text(&rule.selector))
+        .flat_map(|rule| {
+            rule.declarations
+                .iter()
+                .filter(|d| {
+                    (d.property == "user-select" || d.property.ends_with("-user-select"))
+                        && d.value.eq_ignore_ascii_case("none")
+                })
+                .map(move |d| {
+                    sheet.finding(
+                        offender(rule, d),
+                        "Body text can't be selected",
+                        d.offset,
+                        UserSelect::ID,
+                        Category::Accessibility,
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuleLevel;
+    use std::fs::{create_dir, write};
+    use tempfile::TempDir;
+
+    fn offenders(res: &[CheckError]) -> Vec<&str> {
+        res.iter().map(|e| e.offender()).collect()
+    }
+
+    #[test]
+    fn test_parses_rules_and_declarations() {
+        let source = "/* a { color: red } */\nbody { color: black; background: url(\"a;b.png\") }\n@media (min-width: 10em) {\n  .x:hover { COLOR: Red !important }\n}\n@font-face { font-family: X; }";
+        let sheet = Stylesheet::parse(Path::new("style.css"), source);
+
+        assert_eq!(
+            sheet
+                .rules
+                .iter()
+                .map(|r| r.selector.as_str())
+                .collect::<Vec<_>>(),
+            vec!["body", ".x:hover"]
+        );
+        let body = &sheet.rules[0];
+        assert_eq!(body.declarations[1].property, "background");
+        assert_eq!(body.declarations[1].value, "url(\"a;b.png\")");
+
+        let hover = &sheet.rules[1].declarations[0];
+        assert_eq!(hover.property, "color");
+        assert_eq!(hover.value, "Red");
+        assert!(hover.important);
+        let location = sheet.location_of_offset(hover.offset);
+        assert_eq!((location.line, location.column), (4, 14));
+    }
+
+    #[test]
+    fn test_discovers_removed_focus_outline() {
+        let sheet = Stylesheet::parse(
+            Path::new("style.css"),
+            "a:focus { outline: none }\nbutton:focus-visible { outline: 0 }\na:hover { outline: none }\ninput:focus { outline: 2px solid blue }",
+        );
+
+        let res = check_focus_keeps_outline(&sheet);
+
+        assert_eq!(
+            offenders(&res),
+            vec![
+                "a:focus { outline: none }",
+                "button:focus-visible { outline: 0 }"
+            ]
+        );
+        assert_eq!(res[1].location().map(|l| l.line), Some(2));
+    }
+
+    #[test]
+    fn test_focus_within_is_not_a_focus_style() {
+        let sheet = Stylesheet::parse(
+            Path::new("style.css"),
+            "form:focus-within { outline: none }
+a:focus, a:hover { outline: none }",
+        );
+
+        let res = check_focus_keeps_outline(&sheet);
+
+        assert_eq!(offenders(&res), vec!["a:focus, a:hover { outline: none }"]);
+    }
+
+    #[test]
+    fn test_discovers_important_overuse() {
+        let sheet = Stylesheet::parse(
+            Path::new("style.css"),
+            "a { color: red !important; margin: 0 ! important }\np { color: blue !IMPORTANT }",
+        );
+
+        assert!(check_important_overuse(&sheet, 3).is_empty());
+        let res = check_important_overuse(&sheet, 2);
+        assert_eq!(offenders(&res), vec!["p { color: blue !important }"]);
+        assert_eq!(
+            res[0].description(),
+            "Stylesheet uses !important 3 times, more than the 2 allowed"
+        );
+    }
+
+    #[test]
+    fn test_discovers_px_only_font_sizes() {
+        let sheet = Stylesheet::parse(
+            Path::new("style.css"),
+            "p { font-size: 14px }\nh1 { font-size: 2rem }\nsmall { font-size: clamp(12px, 1vw, 16px) }\nbody { font: bold 16px/1.5 serif }\ndiv { padding: 10px }",
+        );
+
+        let res = check_font_sizes_not_only_px(&sheet);
+
+        assert_eq!(
+            offenders(&res),
+            vec![
+                "p { font-size: 14px }",
+                "body { font: bold 16px/1.5 serif }"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discovers_missing_assets() {
+        let site_dir = TempDir::new().expect("could not create temp dir");
+        create_dir(site_dir.path().join("img")).expect("failed to create folder");
+        write(site_dir.path().join("img/bg.png"), "").expect("failed to write file");
+        let site = Site::index(site_dir.path());
+        let path = site_dir.path().join("style.css");
+        let mut sheet = Stylesheet::parse(
+            &path,
+            "@import 'missing.css';\nbody { background: url(img/bg.png) }\n.a { background: url( \"/img/nope.png\" ) }\n/* .b { background: url(commented.png) } */\n.c { background: url(data:image/png;base64,AAAA) }\n.d { mask: url(#mask) }",
+        );
+        sheet.site = Some(&site);
+
+        let res = check_urls_exist(&sheet);
+
+        assert_eq!(offenders(&res), vec!["missing.css", "/img/nope.png"]);
+        assert_eq!(res[1].location().map(|l| (l.line, l.column)), Some((3, 18)));
+    }
+
+    #[test]
+    fn test_discovers_unselectable_text() {
+        let sheet = Stylesheet::parse(
+            Path::new("style.css"),
+            "body { user-select: none }\n.content > p { -webkit-user-select: none }\nbutton, .icon { user-select: none }\np { user-select: text }",
+        );
+
+        let res = check_text_is_selectable(&sheet);
+
+        assert_eq!(
+            offenders(&res),
+            vec![
+                "body { user-select: none }",
+                ".content > p { -webkit-user-select: none }"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rule_levels_are_applied() {
+        let site_dir = TempDir::new().expect("could not create temp dir");
+        let path = site_dir.path().join("style.css");
+        write(
+            &path,
+            "a:focus { outline: none }\np { font-size: 12px; user-select: none }",
+        )
+        .expect("failed to write file");
+        let mut conf = ReaderConfig::default();
+        conf.rules
+            .insert("css-user-select".to_string(), RuleLevel::Off);
+
+        let res = check_css_file(&path, &conf, None);
+
+        assert_eq!(
+            res.iter()
+                .map(|e| (e.rule(), e.severity()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("css-focus-outline", Severity::Error),
+                ("css-px-font-size", Severity::Warning)
+            ]
+        );
+    }
+}
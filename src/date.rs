@@ -0,0 +1,312 @@
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use lazy_static::*;
+use regex::Regex;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+const ISO_DATE_FORMAT: &str = "%Y-%m-%d";
+const MAX_OFFSET: i64 = 1_000_000;
+
+/// A rule describing a set of dates, as written in the `forbidden_dates`
+/// list of the config file.
+///
+/// | rule                       | matches                                  |
+/// |----------------------------|------------------------------------------|
+/// | `2021-04-13`               | exactly that date                        |
+/// | `<1950-01-01`              | strictly before the date                 |
+/// | `<=1950-01-01`             | before or on the date                    |
+/// | `>today`                   | strictly after the date                  |
+/// | `>=today+7d`               | after or on the date                     |
+/// | `2020-01-01..2020-12-31`   | between both dates, inclusive            |
+///
+/// Every date can be the keyword `today` or an ISO date, optionally followed
+/// by any number of offsets like `+7d`, `-2w`, `+1m` or `-1y`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum DateRule {
+    On(DateExpr),
+    Before(DateExpr),
+    OnOrBefore(DateExpr),
+    After(DateExpr),
+    OnOrAfter(DateExpr),
+    Between(DateExpr, DateExpr),
+}
+
+impl DateRule {
+    /// Whether the rule matches `date`, with relative dates counted from `today`.
+    /// Dates that end up out of range match nothing.
+    pub fn matches(&self, date: NaiveDate, today: NaiveDate) -> bool {
+        let resolve = |expr: &DateExpr| expr.resolve(today);
+        let matches = match self {
+            DateRule::On(d) => resolve(d).map(|d| date == d),
+            DateRule::Before(d) => resolve(d).map(|d| date < d),
+            DateRule::OnOrBefore(d) => resolve(d).map(|d| date <= d),
+            DateRule::After(d) => resolve(d).map(|d| date > d),
+            DateRule::OnOrAfter(d) => resolve(d).map(|d| date >= d),
+            DateRule::Between(start, end) => resolve(start)
+                .zip(resolve(end))
+                .map(|(start, end)| start <= date && date <= end),
+        };
+        matches.unwrap_or(false)
+    }
+
+    /// Whether what the rule matches depends on the day it's checked.
+    pub fn uses_today(&self) -> bool {
+        match self {
+            DateRule::On(d)
+            | DateRule::Before(d)
+            | DateRule::OnOrBefore(d)
+            | DateRule::After(d)
+            | DateRule::OnOrAfter(d) => d.base.is_none(),
+            DateRule::Between(start, end) => start.base.is_none() || end.base.is_none(),
+        }
+    }
+}
+
+impl FromStr for DateRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(rest) = s.strip_prefix("<=") {
+            Ok(DateRule::OnOrBefore(rest.parse()?))
+        } else if let Some(rest) = s.strip_prefix('<') {
+            Ok(DateRule::Before(rest.parse()?))
+        } else if let Some(rest) = s.strip_prefix(">=") {
+            Ok(DateRule::OnOrAfter(rest.parse()?))
+        } else if let Some(rest) = s.strip_prefix('>') {
+            Ok(DateRule::After(rest.parse()?))
+        } else if let Some((start, end)) = s.split_once("..") {
+            let (start, end): (DateExpr, DateExpr) = (start.parse()?, end.parse()?);
+            // both are relative to the same day, so their order doesn't change
+            let today = today();
+            if let (Some(first), Some(last)) = (start.resolve(today), end.resolve(today)) {
+                if first > last {
+                    return Err(format!(
+                        "range \"{}\" ends before it starts ({} > {})",
+                        s, first, last
+                    ));
+                }
+            }
+            Ok(DateRule::Between(start, end))
+        } else {
+            Ok(DateRule::On(s.parse()?))
+        }
+    }
+}
+
+pub fn today() -> NaiveDate {
+    Local::today().naive_local()
+}
+
+/// A date as written in a rule: either an ISO 8601 date (`YYYY-MM-DD`) or the
+/// keyword `today`, followed by zero or more offsets such as `+7d` or `-1y`.
+/// `today` is only resolved when the rule is matched, so a long running
+/// `--watch` doesn't keep using the day it was started.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DateExpr {
+    /// `None` for `today`.
+    base: Option<NaiveDate>,
+    /// Amounts along with their unit, one of `d`, `w`, `m` or `y`.
+    offsets: Vec<(i64, String)>,
+}
+
+impl DateExpr {
+    /// The date this stands for on `today`, `None` if it's out of the supported range.
+    pub fn resolve(&self, today: NaiveDate) -> Option<NaiveDate> {
+        self.offsets
+            .iter()
+            .try_fold(self.base.unwrap_or(today), |date, (amount, unit)| {
+                apply_offset(date, *amount, unit)
+            })
+    }
+}
+
+impl FromStr for DateExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE_DATE_EXPR: Regex =
+                Regex::new(r"^(?i:(today)|(\d{4}-\d{2}-\d{2}))((?:\s*[+-]\s*\d+\s*[dwmy])*)$")
+                    .unwrap();
+            static ref RE_OFFSET: Regex = Regex::new(r"([+-])\s*(\d+)\s*([dwmy])").unwrap();
+        }
+
+        let s = s.trim();
+        let caps = RE_DATE_EXPR.captures(s).ok_or_else(|| {
+            format!(
+                "\"{}\" is not a valid date, expected YYYY-MM-DD or today, optionally followed by offsets like +7d",
+                s
+            )
+        })?;
+
+        let base = match caps.get(2) {
+            None => None,
+            Some(iso) => Some(
+                NaiveDate::parse_from_str(iso.as_str(), ISO_DATE_FORMAT).map_err(|e| {
+                    format!(
+                        "\"{}\" is not a valid YYYY-MM-DD date ({})",
+                        iso.as_str(),
+                        e
+                    )
+                })?,
+            ),
+        };
+
+        let mut offsets = vec![];
+        for offset in RE_OFFSET.captures_iter(&caps[3]) {
+            let amount: i64 = offset[2]
+                .parse()
+                .map_err(|_| format!("offset \"{}\" is too large", &offset[0]))?;
+            let amount = if &offset[1] == "-" { -amount } else { amount };
+            offsets.push((amount, offset[3].to_string()));
+        }
+
+        let expr = DateExpr { base, offsets };
+        // checked against today, a rule that's only out of range some days is fine
+        expr.resolve(today())
+            .ok_or_else(|| format!("\"{}\" is out of the supported date range", s))?;
+        Ok(expr)
+    }
+}
+
+fn apply_offset(date: NaiveDate, amount: i64, unit: &str) -> Option<NaiveDate> {
+    // chrono panics on durations this large, and no sensible rule needs them
+    if amount.abs() > MAX_OFFSET {
+        return None;
+    }
+    match unit {
+        "d" => date.checked_add_signed(Duration::days(amount)),
+        "w" => date.checked_add_signed(Duration::weeks(amount)),
+        "m" => add_months(date, amount),
+        "y" => add_months(date, amount.checked_mul(12)?),
+        _ => None,
+    }
+}
+
+/// Adds calendar months, clamping the day to the end of the resulting month
+/// so that e.g. 2021-01-31 + 1 month is 2021-02-28.
+fn add_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let total = i64::from(date.year())
+        .checked_mul(12)?
+        .checked_add(i64::from(date.month0()))?
+        .checked_add(months)?;
+    let year = i32::try_from(total.div_euclid(12)).ok()?;
+    let month = total.rem_euclid(12) as u32 + 1;
+
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(y, m, d)
+    }
+
+    fn date(s: &str) -> DateExpr {
+        s.parse().unwrap()
+    }
+
+    fn parse_date(s: &str) -> Result<NaiveDate, String> {
+        Ok(s.parse::<DateExpr>()?.resolve(today()).unwrap())
+    }
+
+    #[test]
+    fn test_parses_exact_date() {
+        assert_eq!(
+            "0000-01-01".parse::<DateRule>(),
+            Ok(DateRule::On(date("0000-01-01")))
+        );
+    }
+
+    #[test]
+    fn test_parses_ranges() {
+        assert_eq!(
+            "<1950-01-01".parse::<DateRule>(),
+            Ok(DateRule::Before(date("1950-01-01")))
+        );
+        assert_eq!(
+            ">today".parse::<DateRule>(),
+            Ok(DateRule::After(date("today")))
+        );
+        assert_eq!(
+            "2020-01-01..2020-12-31".parse::<DateRule>(),
+            Ok(DateRule::Between(date("2020-01-01"), date("2020-12-31")))
+        );
+    }
+
+    #[test]
+    fn test_parses_inclusive_bounds() {
+        let today = today();
+        let rule = "<=1950-01-01".parse::<DateRule>().unwrap();
+        assert!(rule.matches(ymd(1950, 1, 1), today));
+        assert!(!rule.matches(ymd(1950, 1, 2), today));
+
+        let rule = ">=2021-04-13".parse::<DateRule>().unwrap();
+        assert!(rule.matches(ymd(2021, 4, 13), today));
+        assert!(!rule.matches(ymd(2021, 4, 12), today));
+    }
+
+    #[test]
+    fn test_parses_relative_dates() {
+        assert_eq!(parse_date("today+7d"), Ok(today() + Duration::days(7)));
+        assert_eq!(parse_date("TODAY - 2w"), Ok(today() - Duration::weeks(2)));
+        assert_eq!(parse_date("2021-01-31+1m"), Ok(ymd(2021, 2, 28)));
+        assert_eq!(parse_date("2020-02-29-1y"), Ok(ymd(2019, 2, 28)));
+        assert_eq!(parse_date("2021-04-13+1y-1d"), Ok(ymd(2022, 4, 12)));
+    }
+
+    #[test]
+    fn test_relative_dates_are_resolved_when_matched() {
+        let rule = ">today+7d".parse::<DateRule>().unwrap();
+
+        assert!(rule.matches(ymd(2021, 4, 21), ymd(2021, 4, 13)));
+        assert!(!rule.matches(ymd(2021, 4, 21), ymd(2021, 4, 14)));
+    }
+
+    #[test]
+    fn test_knows_which_rules_use_today() {
+        let uses_today = |s: &str| s.parse::<DateRule>().unwrap().uses_today();
+
+        assert!(uses_today(">today"));
+        assert!(uses_today("<=TODAY-1y"));
+        assert!(uses_today("2021-01-01..today+7d"));
+        assert!(!uses_today("<1950-01-01"));
+        assert!(!uses_today("2021-01-01+1m..2021-12-31"));
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert!("yesterday-ish".parse::<DateRule>().is_err());
+        assert!("<2021-13-01".parse::<DateRule>().is_err());
+        assert!("today+7".parse::<DateRule>().is_err());
+        assert!("today+7h".parse::<DateRule>().is_err());
+        assert!("2021-01-01..".parse::<DateRule>().is_err());
+        assert!("2021-12-31..2021-01-01".parse::<DateRule>().is_err());
+        assert!("today+99999999999999999999d".parse::<DateRule>().is_err());
+        assert!("today+999999999999d".parse::<DateRule>().is_err());
+    }
+
+    #[test]
+    fn test_rules_match() {
+        let today = today();
+        let before = DateRule::Before(date("1950-01-01"));
+        assert!(before.matches(ymd(1949, 12, 31), today));
+        assert!(!before.matches(ymd(1950, 1, 1), today));
+
+        let after = DateRule::After(date("2021-04-13"));
+        assert!(after.matches(ymd(2021, 4, 14), today));
+        assert!(!after.matches(ymd(2021, 4, 13), today));
+
+        let between = DateRule::Between(date("2020-01-01"), date("2020-12-31"));
+        assert!(between.matches(ymd(2020, 1, 1), today));
+        assert!(between.matches(ymd(2020, 12, 31), today));
+        assert!(!between.matches(ymd(2021, 1, 1), today));
+
+        assert!(DateRule::On(date("0000-01-01")).matches(ymd(0, 1, 1), today));
+    }
+}
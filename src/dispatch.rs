@@ -1,15 +1,88 @@
-use crate::error::CheckError;
+use crate::baseline;
+use crate::check::Rule;
+use crate::config::RuleLevel;
+use crate::css;
+use crate::error::{Category, CheckError, Severity};
 use crate::html;
+use crate::markdown;
+use crate::site::Site;
+use crate::suppress;
 use crate::ReaderConfig;
 use std::path::Path;
 
-// If you want to add e.g. CSS checking you'd add
-// Some(e) if e == "css" => css::check_css_file(&path)
-// in the pattern match below. and impl check_css_file in it's own mod
-pub fn check_file(path: &Path, conf: &ReaderConfig) -> Result<(), CheckError> {
+/// Description of a rule, independent of the kind of file it checks.
+pub struct RuleInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub category: Category,
+    pub default_severity: Severity,
+}
+
+impl RuleInfo {
+    fn of<R: Rule + ?Sized>(rule: &R) -> RuleInfo {
+        RuleInfo {
+            id: rule.id(),
+            name: rule.name(),
+            category: rule.category(),
+            default_severity: rule.default_severity(),
+        }
+    }
+}
+
+/// Every rule known to the proof reader, in the order they are run.
+pub fn rules() -> Vec<RuleInfo> {
+    let mut rules = html::rules()
+        .iter()
+        .map(|rule| RuleInfo::of(*rule))
+        .collect::<Vec<_>>();
+    rules.extend(css::rules().iter().map(|rule| RuleInfo::of(*rule)));
+    rules.push(markdown::DRAFT);
+    rules.push(markdown::FRONT_MATTER);
+    rules.push(suppress::UNUSED_SUPPRESSION);
+    rules.push(baseline::STALE_BASELINE);
+    #[cfg(feature = "external-links")]
+    rules.push(crate::external::EXTERNAL_LINK);
+    rules
+}
+
+/// Rules whose findings on a page depend on other files, so they're run on
+/// every page even when only some pages are checked.
+pub const SITE_WIDE_RULES: &[&str] = &[
+    html::InternalLink::ID,
+    html::InternalFragment::ID,
+    css::MissingAsset::ID,
+];
+
+/// The ids of every rule that can be configured in the `[rules]` table.
+pub fn rule_ids() -> Vec<&'static str> {
+    rules().iter().map(|rule| rule.id).collect()
+}
+
+/// A copy of `conf` with every rule turned off except the site-wide ones.
+pub fn site_wide_config(conf: &ReaderConfig) -> ReaderConfig {
+    let mut site_wide = conf.clone();
+    let style_ids = conf.style_rules.iter().map(|r| r.id);
+    for id in rule_ids().into_iter().chain(style_ids) {
+        if !SITE_WIDE_RULES.contains(&id) {
+            site_wide.rules.insert(id.to_string(), RuleLevel::Off);
+        }
+    }
+    site_wide
+}
+
+/// Extensions of the files `check_file` checks.
+pub const CHECKED_EXTENSIONS: &[&str] = &["html", "css", "md", "markdown"];
+
+// To check another kind of file, add a module with its own check_*_file,
+// a match arm for its extension below and add it to CHECKED_EXTENSIONS.
+pub fn check_file(path: &Path, conf: &ReaderConfig, site: &Site) -> Vec<CheckError> {
     let ext = path.extension();
     match ext {
-        Some(e) if e == "html" => html::check_html_file(path, conf),
-        _ => Ok(()),
+        Some(e) if e == "html" => html::check_html_file(path, conf, Some(site)),
+        Some(e) if e == "css" => css::check_css_file(path, conf, Some(site)),
+        Some(e) if e == "md" || e == "markdown" => {
+            markdown::check_markdown_file(path, conf, Some(site))
+        }
+        _ => vec![],
     }
 }
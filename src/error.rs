@@ -1,105 +1,279 @@
 use colored::*;
+use serde_derive::{Deserialize, Serialize};
 use std::error;
 use std::fmt;
 use std::io;
+use std::mem;
+use std::path::Path;
+
+use crate::location::Location;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Category {
+    Content,
+    Accessibility,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Category::Content => f.pad("content"),
+            Category::Accessibility => f.pad("accessibility"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => f.pad("warning"),
+            Severity::Error => f.pad("error"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum CheckError {
     ForbiddenFile {
         path: String,
+        rule: &'static str,
+        severity: Severity,
     },
     ContentError {
         path: String,
         offender: String,
         description: String,
+        location: Option<Location>,
+        rule: &'static str,
+        severity: Severity,
     },
     AccessibilityError {
         path: String,
         offender: String,
         description: String,
+        location: Option<Location>,
+        rule: &'static str,
+        severity: Severity,
     },
-    Io(io::Error),
+    /// A file that couldn't be read, so it wasn't checked.
+    Io { path: String, err: io::Error },
+}
+
+/// Reported as the rule of findings for files that couldn't be checked at all.
+pub const IO_ERROR_RULE: &str = "io-error";
+
+impl CheckError {
+    pub fn io(path: &Path, err: io::Error) -> CheckError {
+        CheckError::Io {
+            path: path.display().to_string(),
+            err,
+        }
+    }
+
+    pub fn rule(&self) -> &'static str {
+        match self {
+            CheckError::ForbiddenFile { rule, .. }
+            | CheckError::ContentError { rule, .. }
+            | CheckError::AccessibilityError { rule, .. } => rule,
+            CheckError::Io { .. } => IO_ERROR_RULE,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            CheckError::ForbiddenFile { path, .. }
+            | CheckError::ContentError { path, .. }
+            | CheckError::AccessibilityError { path, .. }
+            | CheckError::Io { path, .. } => path,
+        }
+    }
+
+    pub fn offender(&self) -> &str {
+        match self {
+            CheckError::ContentError { offender, .. }
+            | CheckError::AccessibilityError { offender, .. } => offender,
+            CheckError::ForbiddenFile { .. } | CheckError::Io { .. } => "",
+        }
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            CheckError::ContentError { location, .. }
+            | CheckError::AccessibilityError { location, .. } => *location,
+            CheckError::ForbiddenFile { .. } | CheckError::Io { .. } => None,
+        }
+    }
+
+    /// The path of the file, followed by the line and column if known.
+    pub fn position(&self) -> String {
+        match self.location() {
+            Some(location) => format!("{}:{}", self.path(), location),
+            None => self.path().to_string(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            CheckError::ContentError { description, .. }
+            | CheckError::AccessibilityError { description, .. } => description.clone(),
+            CheckError::ForbiddenFile { .. } => "File is in a forbidden folder".to_string(),
+            CheckError::Io { err, .. } => err.to_string(),
+        }
+    }
+
+    /// The description followed by what it's about, if there's anything.
+    pub fn message(&self) -> String {
+        match self.offender() {
+            "" => self.description(),
+            offender => format!("{}: {}", self.description(), offender),
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            CheckError::AccessibilityError { .. } => Category::Accessibility,
+            _ => Category::Content,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            CheckError::ForbiddenFile { severity, .. }
+            | CheckError::ContentError { severity, .. }
+            | CheckError::AccessibilityError { severity, .. } => *severity,
+            // not being able to check a file is never something to shrug off
+            CheckError::Io { .. } => Severity::Error,
+        }
+    }
+
+    pub fn with_severity(mut self, level: Severity) -> Self {
+        match &mut self {
+            CheckError::ForbiddenFile { severity, .. }
+            | CheckError::ContentError { severity, .. }
+            | CheckError::AccessibilityError { severity, .. } => *severity = level,
+            CheckError::Io { .. } => (),
+        }
+        self
+    }
+
+    pub fn with_location(mut self, new_location: Option<Location>) -> Self {
+        match &mut self {
+            CheckError::ContentError { location, .. }
+            | CheckError::AccessibilityError { location, .. } => *location = new_location,
+            CheckError::ForbiddenFile { .. } | CheckError::Io { .. } => (),
+        }
+        self
+    }
 }
 
-impl From<io::Error> for CheckError {
-    fn from(err: io::Error) -> CheckError {
-        CheckError::Io(err)
+fn colored_label(label: &str, severity: Severity) -> ColoredString {
+    match severity {
+        Severity::Error => format!("Found {} error", label).red(),
+        Severity::Warning => format!("Found {} warning", label).yellow(),
     }
 }
 
 impl fmt::Display for CheckError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            CheckError::ContentError {
-                path,
-                offender,
-                description,
-            } => {
+            CheckError::ContentError { rule, severity, .. } => {
                 write!(
                     f,
-                    "{}: [{}{}], in file {}",
-                    &"Found content error".red(),
-                    &description,
-                    &offender,
-                    &path
+                    "{}: [{}], in file {} ({})",
+                    colored_label("content", *severity),
+                    self.message(),
+                    self.position(),
+                    rule
                 )
             }
-            CheckError::AccessibilityError {
-                path,
-                offender,
-                description,
-            } => {
+            CheckError::AccessibilityError { rule, severity, .. } => {
                 write!(
                     f,
-                    "{}: [{}{}], in file {}",
-                    &"Found accessiblity error".red(),
-                    &description,
-                    &offender,
-                    &path
+                    "{}: [{}], in file {} ({})",
+                    colored_label("accessiblity", *severity),
+                    self.message(),
+                    self.position(),
+                    rule
                 )
             }
-            CheckError::ForbiddenFile { path } => {
-                write!(f, "{}: {}", &"Found forbidden file".red(), path)
+            CheckError::ForbiddenFile { path, severity, .. } => match severity {
+                Severity::Error => write!(f, "{}: {}", &"Found forbidden file".red(), path),
+                Severity::Warning => write!(f, "{}: {}", &"Found forbidden file".yellow(), path),
+            },
+            CheckError::Io { path, err } => {
+                write!(f, "{} {}: {}", &"Could not read".red(), path, err)
             }
-            CheckError::Io(err) => write!(f, "{}", err),
         }
     }
 }
 
 impl error::Error for CheckError {}
 
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: String,
+        err: io::Error,
+    },
+    Parse(toml::de::Error),
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> ConfigError {
+        ConfigError::Parse(err)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, err } => {
+                write!(f, "{} {}: {}", &"Could not read config".red(), path, err)
+            }
+            ConfigError::Parse(err) => write!(f, "{}: {}", &"Invalid config".red(), err),
+            ConfigError::InvalidValue { key, value, reason } => write!(
+                f,
+                "{}: `{}` (\"{}\") {}",
+                &"Invalid config".red(),
+                key,
+                value,
+                reason
+            ),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
 // Mostly just for testing
 impl PartialEq for CheckError {
     fn eq(&self, other: &Self) -> bool {
         use CheckError::*;
         match (self, other) {
-            (
-                ContentError {
-                    path: ps,
-                    offender: offs,
-                    description: dess,
-                },
-                ContentError {
-                    path: po,
-                    offender: offo,
-                    description: deso,
-                },
-            ) => ps == po && offs == offo && dess == deso,
-            (
-                AccessibilityError {
-                    path: ps,
-                    offender: offs,
-                    description: dess,
-                },
-                AccessibilityError {
-                    path: po,
-                    offender: offo,
-                    description: deso,
-                },
-            ) => ps == po && offs == offo && dess == deso,
-            (ForbiddenFile { path: ps }, ForbiddenFile { path: po }) => ps == po,
-            (Io(_), Io(_)) => true, //all io erros are equal for our purposes
-            _ => false,
+            // io errors can't be compared, the file they're about is enough for our purposes
+            (Io { path: a, .. }, Io { path: b, .. }) => a == b,
+            (Io { .. }, _) | (_, Io { .. }) => false,
+            _ => {
+                mem::discriminant(self) == mem::discriminant(other)
+                    && self.path() == other.path()
+                    && self.offender() == other.offender()
+                    && self.description() == other.description()
+                    && self.location() == other.location()
+                    && self.rule() == other.rule()
+                    && self.severity() == other.severity()
+            }
         }
     }
 }
@@ -0,0 +1,657 @@
+use lazy_static::*;
+use rayon::prelude::*;
+use regex::Regex;
+use scraper::Selector;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::ExternalLinksConfig;
+use crate::dispatch::RuleInfo;
+use crate::error::{Category, CheckError, Severity};
+use crate::html::{Page, LINKING_ELEMENTS};
+use crate::location::Location;
+use crate::suppress::Suppressions;
+use crate::ReaderConfig;
+
+pub const EXTERNAL_LINK: RuleInfo = RuleInfo {
+    id: "external-link",
+    name: "Links to other sites resolve",
+    category: Category::Content,
+    default_severity: Severity::Error,
+};
+
+/// What came back from requesting a URL.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum Response {
+    Status(u16),
+    /// No response at all, like a DNS failure or a timeout.
+    Failed(String),
+}
+
+impl Response {
+    fn is_ok(&self) -> bool {
+        matches!(self, Response::Status(code) if *code < 400)
+    }
+
+    fn is_transient(&self) -> bool {
+        match self {
+            Response::Status(code) => *code == 429 || *code >= 500,
+            Response::Failed(_) => true,
+        }
+    }
+}
+
+/// Sends the actual requests, so that the rest can be tested without a network.
+pub trait HttpClient: Sync {
+    fn request(&self, url: &str) -> Response;
+}
+
+pub struct UreqClient {
+    agent: ureq::Agent,
+}
+
+impl UreqClient {
+    pub fn new(timeout: Duration) -> UreqClient {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(timeout)
+            .user_agent(concat!("proof-reader/", env!("CARGO_PKG_VERSION")))
+            .build();
+        UreqClient { agent }
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn request(&self, url: &str) -> Response {
+        // plenty of servers don't implement HEAD, those get a GET instead
+        let res = match self.agent.head(url).call() {
+            Err(ureq::Error::Status(405, _)) | Err(ureq::Error::Status(501, _)) => {
+                self.agent.get(url).call()
+            }
+            res => res,
+        };
+        match res {
+            Ok(r) => Response::Status(r.status()),
+            Err(ureq::Error::Status(code, _)) => Response::Status(code),
+            Err(e) => Response::Failed(e.to_string()),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    response: Response,
+    checked_at: u64,
+}
+
+/// Responses of earlier runs, kept as a JSON file keyed by URL.
+struct Cache {
+    path: PathBuf,
+    ttl: u64,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl Cache {
+    /// A missing or unreadable cache file just means starting over.
+    fn load(path: &Path, ttl_hours: u64) -> Cache {
+        let ttl = ttl_hours.saturating_mul(60 * 60);
+        let mut entries: HashMap<String, CacheEntry> = match ttl {
+            0 => HashMap::new(),
+            _ => read_to_string(path)
+                .ok()
+                .and_then(|contents| serde_json::from_str(&contents).ok())
+                .unwrap_or_default(),
+        };
+        let now = now();
+        entries.retain(|_, entry| entry.checked_at.saturating_add(ttl) > now);
+
+        Cache {
+            path: path.to_path_buf(),
+            ttl,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn get(&self, url: &str) -> Option<Response> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(url).map(|entry| entry.response.clone())
+    }
+
+    fn insert(&self, url: &str, response: &Response) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            url.to_string(),
+            CacheEntry {
+                response: response.clone(),
+                checked_at: now(),
+            },
+        );
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if self.ttl == 0 {
+            return Ok(());
+        }
+        let entries = self.entries.lock().unwrap();
+        write(&self.path, serde_json::to_string_pretty(&*entries)?)
+    }
+}
+
+/// Spaces out requests to the same host by at least `interval`.
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn wait(&self, host: &str) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_slot.get(host).map_or(now, |next| (*next).max(now));
+            next_slot.insert(host.to_string(), slot + self.interval);
+            slot
+        };
+        let now = Instant::now();
+        if slot > now {
+            sleep(slot - now);
+        }
+    }
+}
+
+fn host_of(url: &str) -> &str {
+    lazy_static! {
+        static ref RE_HOST: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*://([^/?#]*)").unwrap();
+    }
+    RE_HOST
+        .captures(url)
+        .and_then(|caps| caps.get(1))
+        .map_or("", |m| m.as_str())
+}
+
+fn is_external(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+struct LinkChecker<'a> {
+    conf: &'a ExternalLinksConfig,
+    client: &'a dyn HttpClient,
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+    limiter: RateLimiter,
+    cache: Cache,
+}
+
+impl<'a> LinkChecker<'a> {
+    fn new(conf: &'a ExternalLinksConfig, client: &'a dyn HttpClient) -> LinkChecker<'a> {
+        // the patterns were validated along with the rest of the config
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|p| Regex::new(p).ok())
+                .collect::<Vec<_>>()
+        };
+        LinkChecker {
+            conf,
+            client,
+            allow: compile(&conf.allow),
+            deny: compile(&conf.deny),
+            limiter: RateLimiter::new(Duration::from_millis(conf.host_interval_ms)),
+            cache: Cache::load(&conf.cache_file, conf.cache_ttl_hours),
+        }
+    }
+
+    fn should_check(&self, url: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|re| re.is_match(url)))
+            && !self.deny.iter().any(|re| re.is_match(url))
+    }
+
+    fn check(&self, url: &str) -> Response {
+        if let Some(response) = self.cache.get(url) {
+            return response;
+        }
+
+        let mut backoff = Duration::from_millis(self.conf.backoff_ms);
+        let mut response = Response::Failed("not requested".to_string());
+        for attempt in 0..=self.conf.retries {
+            if attempt > 0 {
+                sleep(backoff);
+                backoff *= 2;
+            }
+            self.limiter.wait(host_of(url));
+            response = self.client.request(url);
+            if !response.is_transient() {
+                break;
+            }
+        }
+
+        // transient failures are worth another try on the next run
+        if !response.is_transient() {
+            self.cache.insert(url, &response);
+        }
+        response
+    }
+}
+
+/// A link to another site, and where it was found.
+struct Occurrence {
+    url: String,
+    path: PathBuf,
+    location: Option<Location>,
+}
+
+fn find_external_links(path: &Path) -> io::Result<Vec<Occurrence>> {
+    let contents = read_to_string(path)?;
+    let page = Page::parse(path, &contents);
+    let suppressions = Suppressions::of_page(&page);
+
+    let mut occurrences = vec![];
+    for (selector, attr) in LINKING_ELEMENTS {
+        let selector = Selector::parse(selector).unwrap();
+        for elem in page.document.select(&selector) {
+            let url = elem.value().attr(attr).unwrap_or_default().trim();
+            let location = page.locate(elem);
+            // silenced links aren't even requested
+            if is_external(url) && !suppressions.silence(EXTERNAL_LINK.id, location) {
+                occurrences.push(Occurrence {
+                    url: url.to_string(),
+                    path: path.to_path_buf(),
+                    location,
+                });
+            }
+        }
+    }
+    Ok(occurrences)
+}
+
+/// Requests every distinct link to another site found in the HTML files among `files`,
+/// and reports every link whose URL didn't resolve.
+pub fn check_external_links(
+    files: &[PathBuf],
+    conf: &ReaderConfig,
+    client: &dyn HttpClient,
+) -> Vec<CheckError> {
+    let severity = match conf.rule_severity(EXTERNAL_LINK.id, EXTERNAL_LINK.default_severity) {
+        Some(s) => s,
+        None => return vec![],
+    };
+    let checker = LinkChecker::new(&conf.external_links, client);
+
+    // unreadable files were already reported by the regular checks
+    let occurrences = files
+        .par_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+        .filter_map(|path| find_external_links(path).ok())
+        .flatten()
+        .collect::<Vec<_>>();
+
+    // the fragment never makes it to the server
+    let request_url = |url: &str| url.split('#').next().unwrap_or_default().to_string();
+    let mut urls = occurrences
+        .iter()
+        .map(|o| request_url(&o.url))
+        .filter(|url| checker.should_check(url))
+        .collect::<Vec<_>>();
+    urls.sort();
+    urls.dedup();
+
+    let check_all = || {
+        urls.par_iter()
+            .map(|url| (url.clone(), checker.check(url)))
+            .collect::<HashMap<_, _>>()
+    };
+    let responses = match rayon::ThreadPoolBuilder::new()
+        .num_threads(conf.external_links.concurrency)
+        .build()
+    {
+        Ok(pool) => pool.install(check_all),
+        Err(_) => check_all(),
+    };
+
+    let mut errors = occurrences
+        .into_iter()
+        .filter_map(|o| {
+            let description = match responses.get(&request_url(&o.url))? {
+                r if r.is_ok() => return None,
+                Response::Status(code) => format!("External link is broken (HTTP {})", code),
+                Response::Failed(e) => format!("External link couldn't be reached ({})", e),
+            };
+            Some(CheckError::ContentError {
+                path: o.path.display().to_string(),
+                offender: o.url,
+                description,
+                location: o.location,
+                rule: EXTERNAL_LINK.id,
+                severity,
+            })
+        })
+        .collect::<Vec<_>>();
+    errors.sort_by(|a, b| (a.path(), a.location()).cmp(&(b.path(), b.location())));
+
+    // a cache that can't be written only makes the next run slower
+    if let Err(e) = checker.cache.save() {
+        eprintln!(
+            "Could not write link cache {}: {}",
+            conf.external_links.cache_file.display(),
+            e
+        );
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use tempfile::TempDir;
+
+    /// Answers from a script, and remembers how often every URL was requested.
+    struct FakeClient {
+        responses: Mutex<HashMap<String, Vec<Response>>>,
+        requests: Mutex<HashMap<String, usize>>,
+    }
+
+    impl FakeClient {
+        fn new(script: &[(&str, &[Response])]) -> FakeClient {
+            FakeClient {
+                responses: Mutex::new(
+                    script
+                        .iter()
+                        .map(|(url, responses)| (url.to_string(), responses.to_vec()))
+                        .collect(),
+                ),
+                requests: Mutex::new(HashMap::new()),
+            }
+        }
+
+        fn requests(&self, url: &str) -> usize {
+            *self.requests.lock().unwrap().get(url).unwrap_or(&0)
+        }
+    }
+
+    impl HttpClient for FakeClient {
+        fn request(&self, url: &str) -> Response {
+            *self
+                .requests
+                .lock()
+                .unwrap()
+                .entry(url.to_string())
+                .or_insert(0) += 1;
+            let mut responses = self.responses.lock().unwrap();
+            match responses.get_mut(url) {
+                Some(r) if r.len() > 1 => r.remove(0),
+                Some(r) => r[0].clone(),
+                None => Response::Status(200),
+            }
+        }
+    }
+
+    fn setup_test_site(pages: &[(&str, &str)]) -> (TempDir, Vec<PathBuf>) {
+        let site = TempDir::new().expect("could not create temp dir");
+        let files = pages
+            .iter()
+            .map(|(name, contents)| {
+                let path = site.path().join(name);
+                write(&path, contents).expect("failed to write file");
+                path
+            })
+            .collect();
+        (site, files)
+    }
+
+    fn setup_test_config(site: &TempDir) -> ReaderConfig {
+        let mut conf = ReaderConfig::default();
+        conf.external_links.host_interval_ms = 0;
+        conf.external_links.backoff_ms = 1;
+        conf.external_links.cache_file = site.path().join("cache.json");
+        conf
+    }
+
+    #[test]
+    fn test_reports_broken_links_on_every_page() {
+        let (site, files) = setup_test_site(&[
+            (
+                "a.html",
+                r#"<a href="https://example.com/gone#intro">gone</a> <img src="https://example.com/ok.png" alt="">"#,
+            ),
+            (
+                "b.html",
+                r#"<a href="https://example.com/gone">gone again</a> <a href="/local">local</a>"#,
+            ),
+        ]);
+        let client = FakeClient::new(&[("https://example.com/gone", &[Response::Status(404)])]);
+
+        let res = check_external_links(&files, &setup_test_config(&site), &client);
+
+        assert_eq!(
+            res.iter().map(|e| e.offender()).collect::<Vec<_>>(),
+            vec!["https://example.com/gone#intro", "https://example.com/gone"]
+        );
+        assert_eq!(res[0].description(), "External link is broken (HTTP 404)");
+        // both pages link to the same URL, which is only requested once
+        assert_eq!(client.requests("https://example.com/gone"), 1);
+    }
+
+    #[test]
+    fn test_suppressed_links_are_not_checked() {
+        let (site, files) = setup_test_site(&[(
+            "a.html",
+            r#"<p data-proofreader-ignore="external-link"><a href="https://example.com/a">a</a></p>
+<!-- proof-reader-disable-next-line external-link -->
+<a href="https://example.com/b">b</a>
+<a href="https://example.com/c">c</a>"#,
+        )]);
+        let client = FakeClient::new(&[
+            ("https://example.com/a", &[Response::Status(404)]),
+            ("https://example.com/b", &[Response::Status(404)]),
+            ("https://example.com/c", &[Response::Status(404)]),
+        ]);
+
+        let res = check_external_links(&files, &setup_test_config(&site), &client);
+
+        assert_eq!(
+            res.iter().map(|e| e.offender()).collect::<Vec<_>>(),
+            vec!["https://example.com/c"]
+        );
+        assert_eq!(client.requests("https://example.com/a"), 0);
+    }
+
+    #[test]
+    fn test_retries_transient_failures() {
+        let (site, files) = setup_test_site(&[(
+            "a.html",
+            r#"<a href="https://flaky.example.com/">flaky</a> <a href="https://down.example.com/">down</a>"#,
+        )]);
+        let client = FakeClient::new(&[
+            (
+                "https://flaky.example.com/",
+                &[Response::Status(503), Response::Status(200)],
+            ),
+            (
+                "https://down.example.com/",
+                &[Response::Failed("connection refused".to_string())],
+            ),
+        ]);
+
+        let res = check_external_links(&files, &setup_test_config(&site), &client);
+
+        assert_eq!(client.requests("https://flaky.example.com/"), 2);
+        assert_eq!(client.requests("https://down.example.com/"), 3);
+        assert_eq!(res.len(), 1);
+        assert_eq!(
+            res[0].description(),
+            "External link couldn't be reached (connection refused)"
+        );
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let (site, files) = setup_test_site(&[(
+            "a.html",
+            r#"<a href="https://docs.example.com/a">a</a> <a href="https://docs.example.com/private/b">b</a> <a href="https://other.example.org/">c</a>"#,
+        )]);
+        let client = FakeClient::new(&[]);
+        let mut conf = setup_test_config(&site);
+        conf.external_links.allow = vec![r"^https://docs\.example\.com/".to_string()];
+        conf.external_links.deny = vec!["/private/".to_string()];
+
+        check_external_links(&files, &conf, &client);
+
+        assert_eq!(client.requests("https://docs.example.com/a"), 1);
+        assert_eq!(client.requests("https://docs.example.com/private/b"), 0);
+        assert_eq!(client.requests("https://other.example.org/"), 0);
+    }
+
+    #[test]
+    fn test_cached_results_are_reused() {
+        let (site, files) = setup_test_site(&[(
+            "a.html",
+            r#"<a href="https://example.com/gone">gone</a> <a href="https://down.example.com/">down</a>"#,
+        )]);
+        let conf = setup_test_config(&site);
+        let client = FakeClient::new(&[
+            ("https://example.com/gone", &[Response::Status(404)]),
+            (
+                "https://down.example.com/",
+                &[Response::Failed("timed out".to_string())],
+            ),
+        ]);
+
+        let first = check_external_links(&files, &conf, &client);
+        let second = check_external_links(&files, &conf, &client);
+
+        assert_eq!(first, second);
+        assert_eq!(client.requests("https://example.com/gone"), 1);
+        // failures that might go away aren't cached
+        assert_eq!(client.requests("https://down.example.com/"), 6);
+    }
+
+    #[test]
+    fn test_expired_cache_entries_are_dropped() {
+        let site = TempDir::new().expect("could not create temp dir");
+        let cache_file = site.path().join("cache.json");
+        write(
+            &cache_file,
+            r#"{
+                "https://old.example.com/": { "response": { "Status": 200 }, "checked_at": 0 },
+                "https://new.example.com/": { "response": { "Status": 404 }, "checked_at": 99999999999 }
+            }"#,
+        )
+        .expect("failed to write file");
+
+        let cache = Cache::load(&cache_file, 24);
+
+        assert_eq!(cache.get("https://old.example.com/"), None);
+        assert_eq!(
+            cache.get("https://new.example.com/"),
+            Some(Response::Status(404))
+        );
+    }
+
+    #[test]
+    fn test_rate_limits_per_host() {
+        let limiter = RateLimiter::new(Duration::from_millis(50));
+        let start = Instant::now();
+
+        limiter.wait("a.example.com");
+        limiter.wait("b.example.com");
+        assert!(start.elapsed() < Duration::from_millis(50));
+        limiter.wait("a.example.com");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_finds_hosts() {
+        assert_eq!(
+            host_of("https://example.com:8080/a?b#c"),
+            "example.com:8080"
+        );
+        assert_eq!(host_of("http://example.com"), "example.com");
+        assert_eq!(host_of("/local"), "");
+    }
+
+    /// Serves a fixed status per path on localhost, and 405 to HEAD requests for `/no-head`.
+    fn stub_server(routes: &[(&'static str, u16)]) -> String {
+        let routes = routes.iter().cloned().collect::<HashMap<_, _>>();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind stub server");
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(|s| s.ok()) {
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                let mut header = String::new();
+                while reader.read_line(&mut header).is_ok() && header.trim() != "" {
+                    header.clear();
+                }
+
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default();
+                let path = parts.next().unwrap_or_default();
+                let status = match (method, path) {
+                    ("HEAD", "/no-head") => 405,
+                    (_, "/no-head") => 200,
+                    _ => *routes.get(path).unwrap_or(&404),
+                };
+                let _ = write!(
+                    &stream,
+                    "HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_ureq_client_against_stub_server() {
+        let base = stub_server(&[("/ok", 200), ("/error", 500)]);
+        let client = UreqClient::new(Duration::from_secs(5));
+
+        assert_eq!(
+            client.request(&format!("{}/ok", base)),
+            Response::Status(200)
+        );
+        assert_eq!(
+            client.request(&format!("{}/missing", base)),
+            Response::Status(404)
+        );
+        assert_eq!(
+            client.request(&format!("{}/error", base)),
+            Response::Status(500)
+        );
+        assert_eq!(
+            client.request(&format!("{}/no-head", base)),
+            Response::Status(200)
+        );
+        assert!(matches!(
+            client.request("http://127.0.0.1:1/"),
+            Response::Failed(_)
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+use chrono::NaiveDate;
+use lazy_static::*;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::Generator;
+use crate::html::extract_iso_date;
+
+/// The language a front matter block is written in, told apart by its fences.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    /// Fenced by `+++` lines, like in Zola and Hugo.
+    Toml,
+    /// Fenced by `---` lines, like in Jekyll and Hugo.
+    Yaml,
+}
+
+/// The unparsed front matter at the start of a file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Block<'a> {
+    pub format: Format,
+    /// Offset of the first line after the opening fence.
+    pub offset: usize,
+    pub text: &'a str,
+}
+
+/// Splits off the front matter at the very start of `text`, returning it along with
+/// the offset the body starts at.
+pub fn split(text: &str) -> (Option<Block<'_>>, usize) {
+    let mut lines = text.split_inclusive('\n');
+    let (format, fence, start) = match lines.next() {
+        Some(first) if first.trim_end() == "+++" => (Format::Toml, "+++", first.len()),
+        Some(first) if first.trim_end() == "---" => (Format::Yaml, "---", first.len()),
+        _ => return (None, 0),
+    };
+
+    let mut offset = start;
+    for line in lines {
+        if line.trim_end() == fence {
+            let block = Block {
+                format,
+                offset: start,
+                text: &text[start..offset],
+            };
+            return (Some(block), offset + line.len());
+        }
+        offset += line.len();
+    }
+    // without a closing fence it's not front matter, the generator will complain about it anyway
+    (None, 0)
+}
+
+/// Where a generator keeps the fields the content rules care about.
+struct Preset {
+    /// Keys that may hold the publish date, the first one set is used.
+    dates: &'static [&'static str],
+    /// Table holding every taxonomy, like `[taxonomies]` in Zola.
+    taxonomy_table: Option<&'static str>,
+    /// Top level keys that are taxonomies, for generators without such a table.
+    taxonomy_keys: &'static [&'static str],
+    /// Whether a taxonomy can be a single string of space separated terms.
+    space_separated: bool,
+    /// Folder drafts are kept in, regardless of their front matter.
+    drafts_folder: Option<&'static str>,
+    /// Whether posts are named like `2021-04-13-title.md`, which sets their date.
+    dated_file_names: bool,
+}
+
+fn preset(generator: Generator) -> Preset {
+    match generator {
+        Generator::Zola => Preset {
+            dates: &["date"],
+            taxonomy_table: Some("taxonomies"),
+            taxonomy_keys: &[],
+            space_separated: false,
+            drafts_folder: None,
+            dated_file_names: false,
+        },
+        Generator::Hugo => Preset {
+            dates: &["date", "publishDate"],
+            taxonomy_table: None,
+            taxonomy_keys: &["tags", "categories"],
+            space_separated: false,
+            drafts_folder: None,
+            dated_file_names: false,
+        },
+        Generator::Jekyll => Preset {
+            dates: &["date"],
+            taxonomy_table: None,
+            taxonomy_keys: &["tags", "categories"],
+            space_separated: true,
+            drafts_folder: Some("_drafts"),
+            dated_file_names: true,
+        },
+    }
+}
+
+/// The fields of the front matter that content rules look at.
+#[derive(Debug)]
+pub struct FrontMatter<'a> {
+    pub block: Block<'a>,
+    pub title: Option<String>,
+    pub date: Option<NaiveDate>,
+    /// Every taxonomy with its terms, keyed by its name like `tags`.
+    pub taxonomies: BTreeMap<String, Vec<String>>,
+    pub draft: bool,
+}
+
+impl<'a> FrontMatter<'a> {
+    /// Reads the front matter of the file at `path` the way `generator` would.
+    pub fn read(path: &Path, block: Block<'a>, generator: Generator) -> Result<Self, String> {
+        let values = match block.format {
+            Format::Toml => block
+                .text
+                .parse::<toml::Value>()
+                .map(from_toml)
+                .map_err(|e| e.to_string())?,
+            Format::Yaml => serde_yaml::from_str::<Value>(block.text).map_err(|e| e.to_string())?,
+        };
+        // an empty block is fine, it just doesn't set anything
+        let values = match values {
+            Value::Object(map) => map,
+            Value::Null => Default::default(),
+            _ => return Err("Front matter isn't a table".to_string()),
+        };
+        let preset = preset(generator);
+
+        let title = values
+            .get("title")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let date = preset
+            .dates
+            .iter()
+            .find_map(|key| values.get(*key).and_then(Value::as_str))
+            .and_then(extract_iso_date)
+            .or_else(|| {
+                let name = path.file_name()?.to_string_lossy();
+                extract_iso_date(&name).filter(|_| preset.dated_file_names)
+            })
+            .and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+
+        let mut taxonomies = BTreeMap::new();
+        let table = match preset.taxonomy_table {
+            Some(key) => values.get(key).and_then(Value::as_object),
+            None => Some(&values),
+        };
+        for (name, terms) in table.into_iter().flatten() {
+            if preset.taxonomy_table.is_none() && !preset.taxonomy_keys.contains(&name.as_str()) {
+                continue;
+            }
+            let terms = match terms {
+                Value::Array(terms) => terms.iter().filter_map(term).collect(),
+                Value::String(s) if preset.space_separated => {
+                    s.split_whitespace().map(str::to_string).collect()
+                }
+                terms => term(terms).into_iter().collect(),
+            };
+            taxonomies.insert(name.clone(), terms);
+        }
+
+        let in_drafts_folder = preset
+            .drafts_folder
+            .is_some_and(|folder| path.components().any(|c| c.as_os_str() == folder));
+        let draft = values.get("draft").and_then(Value::as_bool) == Some(true)
+            || values.get("published").and_then(Value::as_bool) == Some(false)
+            || in_drafts_folder;
+
+        Ok(FrontMatter {
+            block,
+            title,
+            date,
+            taxonomies,
+            draft,
+        })
+    }
+
+    pub fn tags(&self) -> &[String] {
+        self.taxonomies.get("tags").map_or(&[], |t| t.as_slice())
+    }
+
+    /// Offset in the file of the line that sets `key`, or of the block if it isn't set.
+    pub fn key_offset(&self, key: &str) -> usize {
+        lazy_static! {
+            static ref RE_KEY: Regex =
+                Regex::new(r"(?m)^[ \t]*([A-Za-z0-9_-]+)[ \t]*[=:]").unwrap();
+        }
+        let at = RE_KEY
+            .captures_iter(self.block.text)
+            .find(|c| &c[1] == key)
+            .and_then(|c| c.get(1))
+            .map_or(0, |m| m.start());
+        self.block.offset + at
+    }
+
+    /// Offset in the file of `value` as written after `key`, or of `key` if it can't be found.
+    pub fn value_offset(&self, key: &str, value: &str) -> usize {
+        let key_offset = self.key_offset(key);
+        let after_key = &self.block.text[key_offset - self.block.offset..];
+        let value = Regex::new(&format!(r"\b{}\b", regex::escape(value))).unwrap();
+        value
+            .find(after_key)
+            .map_or(key_offset, |m| key_offset + m.start())
+    }
+}
+
+fn term(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Converts TOML to the same values YAML is read into, with dates as strings.
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(from_toml).collect()),
+        toml::Value::Table(t) => {
+            Value::Object(t.into_iter().map(|(k, v)| (k, from_toml(v))).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &str, text: &'static str, generator: Generator) -> FrontMatter<'static> {
+        let block = split(text).0.expect("should have front matter");
+        FrontMatter::read(Path::new(path), block, generator).expect("front matter should parse")
+    }
+
+    fn date(s: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+    }
+
+    #[test]
+    fn test_splits_front_matter() {
+        let (block, body_offset) = split("+++\ntitle = \"Hi\"\n+++\n# Hi\n");
+        assert_eq!(
+            block,
+            Some(Block {
+                format: Format::Toml,
+                offset: 4,
+                text: "title = \"Hi\"\n"
+            })
+        );
+        assert_eq!(body_offset, 21);
+
+        let (block, _) = split("---\ntitle: Hi\n---\n");
+        assert_eq!(
+            block.map(|b| (b.format, b.text)),
+            Some((Format::Yaml, "title: Hi\n"))
+        );
+
+        assert_eq!(split("# No front matter\n+++\n"), (None, 0));
+        assert_eq!(split("+++\ntitle = \"never closed\"\n"), (None, 0));
+    }
+
+    #[test]
+    fn test_reads_zola_front_matter() {
+        let front_matter = read(
+            "content/post.md",
+            "+++\ntitle = \"Post\"\ndate = 2021-04-13T10:00:00Z\ndraft = true\n\n[taxonomies]\ntags = [\"rust\", \"wip\"]\nseries = [\"intro\"]\n+++\n",
+            Generator::Zola,
+        );
+
+        assert_eq!(front_matter.title.as_deref(), Some("Post"));
+        assert_eq!(front_matter.date, date("2021-04-13"));
+        assert_eq!(front_matter.tags(), ["rust", "wip"]);
+        assert_eq!(front_matter.taxonomies["series"], ["intro"]);
+        assert!(front_matter.draft);
+    }
+
+    #[test]
+    fn test_reads_hugo_front_matter() {
+        let front_matter = read(
+            "content/post.md",
+            "---\ntitle: Post\npublishDate: 2021-04-13\ntags: [rust, wip]\ncategories: notes\nauthor: me\n---\n",
+            Generator::Hugo,
+        );
+
+        assert_eq!(front_matter.date, date("2021-04-13"));
+        assert_eq!(front_matter.tags(), ["rust", "wip"]);
+        assert_eq!(front_matter.taxonomies["categories"], ["notes"]);
+        assert!(!front_matter.taxonomies.contains_key("author"));
+        assert!(!front_matter.draft);
+    }
+
+    #[test]
+    fn test_reads_jekyll_front_matter() {
+        let front_matter = read(
+            "_posts/2021-04-13-post.md",
+            "---\ntitle: Post\ntags: rust wip\npublished: false\n---\n",
+            Generator::Jekyll,
+        );
+        assert_eq!(front_matter.date, date("2021-04-13"));
+        assert_eq!(front_matter.tags(), ["rust", "wip"]);
+        assert!(front_matter.draft);
+
+        let front_matter = read(
+            "_drafts/post.md",
+            "---\ntitle: Post\n---\n",
+            Generator::Jekyll,
+        );
+        assert_eq!(front_matter.date, None);
+        assert!(front_matter.draft);
+    }
+
+    #[test]
+    fn test_points_at_keys_and_values() {
+        let front_matter = read(
+            "post.md",
+            "---\ntitle: Post\ntags:\n  - rust\n  - wip\n---\n",
+            Generator::Hugo,
+        );
+
+        assert_eq!(front_matter.key_offset("title"), 4);
+        assert_eq!(front_matter.key_offset("tags"), 16);
+        assert_eq!(front_matter.value_offset("tags", "wip"), 35);
+        assert_eq!(front_matter.key_offset("date"), 4);
+    }
+}
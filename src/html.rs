@@ -1,64 +1,321 @@
-use scraper::html::Select;
 use scraper::{ElementRef, Html, Selector};
 use selectors::attr::CaseSensitivity;
 use std::collections::HashSet;
 use std::fs::read_to_string;
 use std::path::Path;
 
+use chrono::NaiveDate;
+
+use crate::check::{run_checks, Check, CheckResult};
+use crate::config::{ReadabilityConfig, SpellingConfig};
+use crate::contrast::find_low_contrast;
+use crate::date::{today, DateRule};
+use crate::error::Severity;
+use crate::location::{Location, SourceMap};
+use crate::prose::Prose;
+use crate::readability::measure;
+use crate::site::{anchors, Site};
+use crate::spelling::find_misspellings;
+use crate::style::check_style_rules;
+use crate::suppress::apply_suppressions;
 use crate::CheckError;
 use crate::ReaderConfig;
 
 use lazy_static::*;
 use regex::Regex;
-type CheckResult = Result<(), CheckError>;
 
-pub trait Check {
-    fn select(&self, document: &Html) -> Select;
-    fn check(&self, selection: &Select) -> CheckResult;
+/// A rule run against every HTML page, see `check::Check`.
+pub type HtmlCheck = dyn for<'a> Check<Page<'a>>;
+
+// Declares a unit struct implementing `Check` for one of the check functions below.
+macro_rules! html_check {
+    ($($rule:tt)*) => {
+        check!(Page, $($rule)*);
+    };
 }
 
-pub fn check_html_file(path: &Path, conf: &ReaderConfig) -> CheckResult {
-    check_for_forbidden_files(path)?;
+html_check!(
+    ForbiddenFolder,
+    "forbidden-folder",
+    "File is in a forbidden folder",
+    Content,
+    Error,
+    |page, conf| check_for_forbidden_files(page.path, &conf.forbidden_folders)
+);
+html_check!(
+    ForbiddenTag,
+    "forbidden-tag",
+    "Page has a forbidden tag",
+    Content,
+    Error,
+    |page, conf| check_forbidden_tags(page, &conf.forbidden_tags, &conf.tag_url_regex)
+);
+html_check!(
+    ForbiddenDate,
+    "forbidden-date",
+    "Page has a forbidden publish date",
+    Content,
+    Error,
+    |page, conf| match &conf.pub_date_selector {
+        Some(selector) => check_for_invalid_publish_dates(page, selector, &conf.date_rules),
+        None => vec![],
+    }
+);
+html_check!(
+    ImgAlt,
+    "img-alt",
+    "Images have an alt attribute",
+    Accessibility,
+    Error,
+    |page, _conf| check_img_tags_have_alts(page)
+);
+html_check!(
+    NoTitleAttr,
+    "no-title-attr",
+    "Tags don't rely on the title attribute",
+    Accessibility,
+    Error,
+    |page, _conf| check_tags_dont_have_title_attr(page)
+);
+html_check!(
+    NoDisabledZoom,
+    "no-disabled-zoom",
+    "Page doesn't disable zooming",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_disabe_zoom(page)
+);
+html_check!(
+    PageTitle,
+    "page-title",
+    "Page has a title",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_has_title(page)
+);
+html_check!(
+    HtmlLang,
+    "html-lang",
+    "Page has a lang attribute",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_has_lang_attr(page)
+);
+html_check!(
+    NoPositiveTabindex,
+    "no-positive-tabindex",
+    "Tags don't have a positive tabindex",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_positive_tabindex(page)
+);
+html_check!(
+    NoAutofocus,
+    "no-autofocus",
+    "Tags don't have the autofocus attribute",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_autofocus(page)
+);
+html_check!(
+    SingleH1,
+    "single-h1",
+    "Page has at most one h1 heading",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_multiple_h1_elements(page)
+);
+html_check!(
+    HeadingOrder,
+    "heading-order",
+    "Headings start at h1, don't skip levels and aren't empty",
+    Accessibility,
+    Error,
+    |page, _conf| check_heading_hierarchy(page)
+);
+html_check!(
+    LinkHref,
+    "link-href",
+    "Links have an href attribute",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_hrefless_link(page)
+);
+html_check!(
+    FigureCaption,
+    "figure-caption",
+    "Figures have a caption",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_captionless_figure(page)
+);
+html_check!(
+    TableCaption,
+    "table-caption",
+    "Tables have a caption",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_captionless_table(page)
+);
+html_check!(
+    FormLabel,
+    "form-label",
+    "Form inputs have a label",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_labelless_form_elements(page)
+);
+html_check!(
+    NoAutoplay,
+    "no-autoplay",
+    "Media doesn't autoplay",
+    Accessibility,
+    Error,
+    |page, _conf| check_page_doesnt_have_autoplay_media(page)
+);
+html_check!(
+    ColorContrast,
+    "color-contrast",
+    "Text contrasts enough with its background",
+    Accessibility,
+    Error,
+    |page, _conf| check_color_contrast(page)
+);
+html_check!(
+    InternalLink,
+    "internal-link",
+    "Links within the site point to existing files",
+    Content,
+    Error,
+    |page, _conf| check_internal_links(page)
+);
+html_check!(
+    InternalFragment,
+    "internal-fragment",
+    "Fragment links point to existing anchors",
+    Content,
+    Error,
+    |page, _conf| check_internal_fragments(page)
+);
+html_check!(
+    Spelling,
+    "spelling",
+    "Prose is spelled correctly",
+    Content,
+    Warning,
+    |page, conf| check_spelling(page, &conf.spelling)
+);
+html_check!(
+    DoubledWord,
+    "doubled-word",
+    "Words aren't accidentally repeated",
+    Content,
+    Warning,
+    |page, _conf| check_doubled_words(page)
+);
+html_check!(
+    Readability,
+    "readability",
+    "Prose is easy enough to read",
+    Content,
+    Warning,
+    |page, conf| check_readability(page, &conf.readability)
+);
+
+static RULES: &[&HtmlCheck] = &[
+    &ForbiddenFolder,
+    &ForbiddenTag,
+    &ForbiddenDate,
+    &ImgAlt,
+    &NoTitleAttr,
+    &NoDisabledZoom,
+    &PageTitle,
+    &HtmlLang,
+    &NoPositiveTabindex,
+    &NoAutofocus,
+    &SingleH1,
+    &HeadingOrder,
+    &LinkHref,
+    &FigureCaption,
+    &TableCaption,
+    &FormLabel,
+    &NoAutoplay,
+    &ColorContrast,
+    &InternalLink,
+    &InternalFragment,
+    &Spelling,
+    &DoubledWord,
+    &Readability,
+];
+
+/// All html rules, in the order they are run.
+pub fn rules() -> &'static [&'static HtmlCheck] {
+    RULES
+}
 
-    let contents = read_to_string(path)?;
-    let html = Html::parse_document(&contents);
+/// A parsed HTML file, along with what's needed to point back into its source.
+pub struct Page<'a> {
+    pub path: &'a Path,
+    pub source: &'a str,
+    pub document: Html,
+    /// The site the page is part of, pages checked on their own
+    /// have nothing to resolve links against.
+    pub site: Option<&'a Site>,
+    /// The level of a heading rendered before the document, like the title
+    /// of a markdown page that the templates turn into its h1.
+    pub outer_heading: Option<usize>,
+    source_map: SourceMap,
+}
 
-    check_forbidden_tags(path, &html)?;
-    match &conf.pub_date_selector {
-        None => Ok(()),
-        Some(selector) => {
-            check_for_invalid_publish_dates(path, &html, selector, &conf.forbidden_dates)
+impl<'a> Page<'a> {
+    pub fn parse(path: &'a Path, source: &'a str) -> Page<'a> {
+        let document = Html::parse_document(source);
+        let source_map = SourceMap::new(source, &document);
+        Page {
+            path,
+            source,
+            document,
+            site: None,
+            outer_heading: None,
+            source_map,
         }
-    }?;
-
-    check_img_tags_have_alts(path, &html)?;
-    check_tags_dont_have_title_attr(path, &html)?;
-    check_page_doesnt_disabe_zoom(path, &html)?;
-    check_page_has_title(path, &html)?;
-    check_page_has_lang_attr(path, &html)?;
-    check_page_doesnt_have_positive_tabindex(path, &html)?;
-    check_page_doesnt_have_autofocus(path, &html)?;
-    check_page_doesnt_have_multiple_h1_elements(path, &html)?;
-    check_page_doesnt_have_hrefless_link(path, &html)?;
-    check_page_doesnt_have_captionless_figure(path, &html)?;
-    check_page_doesnt_have_captionless_table(path, &html)?;
-    check_page_doesnt_have_labelless_form_elements(path, &html)?;
-    check_page_doesnt_have_autoplay_media(path, &html)?;
-    Ok(())
-}
+    }
 
-fn extract_tag_name_from_url(url: &str) -> Option<String> {
-    lazy_static! {
-        static ref RE_TAGS: Regex = Regex::new("tags/([-a-zA-Z0-9]+)").unwrap();
+    pub fn locate(&self, element: ElementRef) -> Option<Location> {
+        self.source_map.locate(element)
     }
-    RE_TAGS
+
+    pub fn location_of_offset(&self, offset: usize) -> Location {
+        self.source_map.location_of_offset(self.source, offset)
+    }
+}
+
+pub fn check_html_file(path: &Path, conf: &ReaderConfig, site: Option<&Site>) -> CheckResult {
+    let contents = match read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return vec![CheckError::io(path, e)],
+    };
+    let mut page = Page::parse(path, &contents);
+    page.site = site;
+
+    let (mut errors, mut ran) = run_checks(rules(), &page, conf);
+
+    let (style_errors, style_ran) = check_style_rules(&page, conf);
+    errors.extend(style_errors);
+    ran.extend(style_ran);
+
+    apply_suppressions(&page, errors, &ran, conf)
+}
+
+fn extract_tag_name_from_url(url: &str, tag_re: &Regex) -> Option<String> {
+    tag_re
         .captures_iter(url)
         .next()?
         .get(1)
         .map(|m| m.as_str().to_string())
 }
 
-fn extract_iso_date(text: &str) -> Option<String> {
+pub fn extract_iso_date(text: &str) -> Option<String> {
     lazy_static! {
         static ref RE_DATE: Regex = Regex::new(r"(\d{4}-\d{2}-\d{2})").unwrap();
     }
@@ -69,327 +326,630 @@ fn extract_iso_date(text: &str) -> Option<String> {
         .map(|m| m.as_str().to_string())
 }
 
-fn check_for_forbidden_files(path: &Path) -> CheckResult {
-    let forbidden_folders = {
-        let mut set = HashSet::new();
-        set.insert("unpublished");
-        set.insert("publish-queue");
-        set
-    };
+fn check_for_forbidden_files(path: &Path, forbidden_folders: &[String]) -> CheckResult {
+    let forbidden = path.components().any(|comp| {
+        let comp = comp.as_os_str().to_string_lossy();
+        forbidden_folders.iter().any(|f| *f == comp)
+    });
 
-    for comp in path.components() {
-        if forbidden_folders.contains(&comp.as_os_str().to_str().unwrap()) {
-            return Err(CheckError::ForbiddenFile {
-                path: path.display().to_string(),
-            });
-        }
+    if forbidden {
+        vec![CheckError::ForbiddenFile {
+            path: path.display().to_string(),
+            rule: ForbiddenFolder::ID,
+            severity: Severity::Error,
+        }]
+    } else {
+        vec![]
     }
-
-    Ok(())
 }
 
+/// Built pages have no front matter left, so their publish date is scraped from the
+/// element the theme puts it in. Markdown sources are checked against their front matter.
 fn check_for_invalid_publish_dates(
-    path: &Path,
-    document: &Html,
+    page: &Page,
     pub_date_selector: &str,
-    forbidden_dates: &[String],
+    rules: &[DateRule],
 ) -> CheckResult {
     let div_selector = Selector::parse(pub_date_selector).unwrap();
-
-    for div in document.select(&div_selector) {
-        let div_text = &div.text().collect::<Vec<_>>().join("");
-        if let Some(publish_date) = extract_iso_date(div_text) {
-            if forbidden_dates.contains(&publish_date) {
-                return Err(CheckError::ContentError {
-                    path: path.display().to_string(),
-                    offender: publish_date,
-                    description: "Forbidden publish date".to_string(),
-                });
-            }
-        }
-    }
-    Ok(())
+    let today = today();
+
+    page.document
+        .select(&div_selector)
+        .filter_map(|div| {
+            extract_iso_date(&div.text().collect::<Vec<_>>().join("")).map(|date| (div, date))
+        })
+        .filter(|(_, publish_date)| {
+            NaiveDate::parse_from_str(publish_date, "%Y-%m-%d")
+                .map(|date| rules.iter().any(|r| r.matches(date, today)))
+                .unwrap_or(false)
+        })
+        .map(|(div, publish_date)| CheckError::ContentError {
+            path: page.path.display().to_string(),
+            offender: publish_date,
+            description: "Forbidden publish date".to_string(),
+            location: page.locate(div),
+            rule: ForbiddenDate::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
 
-fn check_img_tags_have_alts(path: &Path, document: &Html) -> CheckResult {
+fn check_img_tags_have_alts(page: &Page) -> CheckResult {
     let img_selector = Selector::parse("img").unwrap();
 
-    for img in document.select(&img_selector) {
-        if img.value().attr("alt").is_none() {
-            return Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: img.html(),
-                description: "Image tag without alt".to_string(),
-            });
-        };
-    }
-    Ok(())
+    page.document
+        .select(&img_selector)
+        .filter(|img| img.value().attr("alt").is_none())
+        .map(|img| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: img.html(),
+            description: "Image tag without alt".to_string(),
+            location: page.locate(img),
+            rule: ImgAlt::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
 
-fn check_tags_dont_have_title_attr(path: &Path, document: &Html) -> CheckResult {
+fn check_tags_dont_have_title_attr(page: &Page) -> CheckResult {
     let tag_selector = Selector::parse("*").unwrap();
 
-    for tag in document.select(&tag_selector) {
-        match (tag.value().attr("title"), tag.value().attr("alt")) {
-            (Some(t), Some(alt)) => {
-                if t != alt {
-                    return Err(CheckError::AccessibilityError {
-                        path: path.display().to_string(),
-                        offender: tag.html(),
-                        description: "Title and alt attributes should be equal".to_string(),
-                    });
-                }
-            }
-            (Some(_), None) => {
-                return Err(CheckError::AccessibilityError {
-                    path: path.display().to_string(),
-                    offender: tag.html(),
-                    description: "Tag in page has title attr".to_string(),
-                })
-            }
-            _ => (),
-        }
-    }
-    Ok(())
+    page.document
+        .select(&tag_selector)
+        .filter_map(|tag| {
+            let description = match (tag.value().attr("title"), tag.value().attr("alt")) {
+                (Some(t), Some(alt)) if t != alt => "Title and alt attributes should be equal",
+                (Some(_), None) => "Tag in page has title attr",
+                _ => return None,
+            };
+            Some(CheckError::AccessibilityError {
+                path: page.path.display().to_string(),
+                offender: tag.html(),
+                description: description.to_string(),
+                location: page.locate(tag),
+                rule: NoTitleAttr::ID,
+                severity: Severity::Error,
+            })
+        })
+        .collect()
 }
-fn check_page_doesnt_disabe_zoom(path: &Path, document: &Html) -> CheckResult {
+fn check_page_doesnt_disabe_zoom(page: &Page) -> CheckResult {
     // #<meta name="viewport" content="width=device-width, initial-scale=1.0, maximum-scale=1.0, user-scalable=no" />
     let meta_selector = Selector::parse("meta").unwrap();
     lazy_static! {
         static ref ZOOM_RE: Regex = Regex::new(r"user-scalable\s*=\s*(no|0)").unwrap();
     }
 
-    for tag in document.select(&meta_selector) {
-        match tag.value().attr("content") {
-            None => (),
-            Some(c) => {
-                if ZOOM_RE.is_match(c) {
-                    return Err(CheckError::AccessibilityError {
-                        path: path.display().to_string(),
-                        offender: "".to_string(),
-                        description: "Page disables zoom".to_string(),
-                    });
-                } else {
-                }
-            }
-        };
-    }
-    Ok(())
+    page.document
+        .select(&meta_selector)
+        .filter(|tag| {
+            tag.value()
+                .attr("content")
+                .is_some_and(|c| ZOOM_RE.is_match(c))
+        })
+        .map(|tag| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: "".to_string(),
+            description: "Page disables zoom".to_string(),
+            location: page.locate(tag),
+            rule: NoDisabledZoom::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
-fn check_page_has_title(path: &Path, document: &Html) -> CheckResult {
+fn check_page_has_title(page: &Page) -> CheckResult {
     let head_selector = Selector::parse("head").unwrap();
     let title_selector = Selector::parse("title").unwrap();
 
     //we asume the html is valid and thus has exactly 1 head tag
-    let head_section = document.select(&head_selector).next().unwrap();
+    let head_section = page.document.select(&head_selector).next().unwrap();
     let title_tag = head_section.select(&title_selector).next();
 
     match title_tag {
-        Some(_) => Ok(()),
-        None => Err(CheckError::AccessibilityError {
-            path: path.display().to_string(),
+        Some(_) => vec![],
+        None => vec![CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
             offender: "".to_string(),
             description: "Page is missing a title tag".to_string(),
-        }),
+            location: page.locate(head_section),
+            rule: PageTitle::ID,
+            severity: Severity::Error,
+        }],
     }
 }
-fn check_page_has_lang_attr(path: &Path, document: &Html) -> CheckResult {
-    match document.root_element().value().attr("lang") {
-        Some(_) => Ok(()),
-        None => Err(CheckError::AccessibilityError {
-            path: path.display().to_string(),
+fn check_page_has_lang_attr(page: &Page) -> CheckResult {
+    let root = page.document.root_element();
+    match root.value().attr("lang") {
+        Some(_) => vec![],
+        None => vec![CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
             offender: "".to_string(),
             description: "Page doesn't have lang attribute".to_string(),
-        }),
+            location: page.locate(root),
+            rule: HtmlLang::ID,
+            severity: Severity::Error,
+        }],
     }
 }
-fn check_page_doesnt_have_positive_tabindex(path: &Path, document: &Html) -> CheckResult {
+fn check_page_doesnt_have_positive_tabindex(page: &Page) -> CheckResult {
     let tag_selector = Selector::parse("*").unwrap();
 
-    for tag in document.select(&tag_selector) {
-        match tag.value().attr("tabindex") {
-            None => (),
-            Some(v) => {
-                let tabindex = v.parse::<i32>().unwrap();
-                if tabindex > 0 {
-                    return Err(CheckError::AccessibilityError {
-                        path: path.display().to_string(),
-                        offender: tag.html(),
-                        description: "Page has tag with prositive tab index".to_string(),
-                    });
-                }
-            }
-        };
-    }
-
-    Ok(())
+    page.document
+        .select(&tag_selector)
+        .filter(|tag| {
+            tag.value()
+                .attr("tabindex")
+                .and_then(|v| v.trim().parse::<i32>().ok())
+                .is_some_and(|tabindex| tabindex > 0)
+        })
+        .map(|tag| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: tag.html(),
+            description: "Page has tag with prositive tab index".to_string(),
+            location: page.locate(tag),
+            rule: NoPositiveTabindex::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
-fn check_page_doesnt_have_autofocus(path: &Path, document: &Html) -> CheckResult {
+fn check_page_doesnt_have_autofocus(page: &Page) -> CheckResult {
     let tag_selector = Selector::parse("*").unwrap();
 
-    for tag in document.select(&tag_selector) {
-        if tag.value().attr("autofocus").is_some() {
-            return Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: tag.html(),
-                description: "Tag in page has autofocus attr".to_string(),
-            });
-        };
-    }
-    Ok(())
+    page.document
+        .select(&tag_selector)
+        .filter(|tag| tag.value().attr("autofocus").is_some())
+        .map(|tag| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: tag.html(),
+            description: "Tag in page has autofocus attr".to_string(),
+            location: page.locate(tag),
+            rule: NoAutofocus::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
 
-fn check_page_doesnt_have_multiple_h1_elements(path: &Path, document: &Html) -> CheckResult {
+fn check_page_doesnt_have_multiple_h1_elements(page: &Page) -> CheckResult {
     let h1_selector = Selector::parse("h1").unwrap();
 
-    let mut h1_iter = document.select(&h1_selector);
-    match h1_iter.next() {
-        None => Ok(()),
-        Some(_) => match h1_iter.next() {
-            None => Ok(()),
-            Some(h) => Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: h.html(),
-                description: "Has multiple h1 headings".to_string(),
-            }),
-        },
-    }
+    // the first h1 is fine, every one after that is a violation
+    page.document
+        .select(&h1_selector)
+        .skip(1)
+        .map(|h| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: h.html(),
+            description: "Has multiple h1 headings".to_string(),
+            location: page.locate(h),
+            rule: SingleH1::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
-fn check_page_doesnt_have_hrefless_link(path: &Path, document: &Html) -> CheckResult {
-    let link_selector = Selector::parse("a").unwrap();
-
-    for link in document.select(&link_selector) {
-        if link.value().attr("href").is_none() {
-            return Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: link.html(),
-                description: "Link is missing href attribute".to_string(),
+fn check_heading_hierarchy(page: &Page) -> CheckResult {
+    let heading_selector = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
+    let img_selector = Selector::parse("img[alt]").unwrap();
+
+    let mut errors = vec![];
+    let mut previous = page.outer_heading;
+    for heading in page.document.select(&heading_selector) {
+        let name = heading.value().name();
+        let level = name[1..].parse::<usize>().unwrap_or(1);
+        let text = heading.text().collect::<String>();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let mut problems = vec![];
+        match previous {
+            None if level > 1 => {
+                problems.push(format!("Page starts with an {} instead of an h1", name))
+            }
+            Some(p) if level > p + 1 => {
+                problems.push(format!("Heading skips from h{} to {}", p, name))
+            }
+            _ => (),
+        }
+        // images with alt text or an aria-label also give a heading a name
+        let labelled = heading
+            .value()
+            .attr("aria-label")
+            .is_some_and(|l| !l.trim().is_empty())
+            || heading.select(&img_selector).any(|img| {
+                !img.value()
+                    .attr("alt")
+                    .unwrap_or_default()
+                    .trim()
+                    .is_empty()
             });
+        if text.is_empty() && !labelled {
+            problems.push("Heading is empty".to_string());
+        }
+        previous = Some(level);
+
+        let offender = if text.is_empty() {
+            format!("<{}>", name)
+        } else {
+            format!("<{}> {}", name, text)
         };
+        errors.extend(
+            problems
+                .into_iter()
+                .map(|description| CheckError::AccessibilityError {
+                    path: page.path.display().to_string(),
+                    offender: offender.clone(),
+                    description,
+                    location: page.locate(heading),
+                    rule: HeadingOrder::ID,
+                    severity: Severity::Error,
+                }),
+        );
     }
-    Ok(())
+    errors
 }
+fn check_page_doesnt_have_hrefless_link(page: &Page) -> CheckResult {
+    let link_selector = Selector::parse("a").unwrap();
 
-fn check_page_doesnt_have_captionless_table(path: &Path, document: &Html) -> CheckResult {
+    page.document
+        .select(&link_selector)
+        .filter(|link| link.value().attr("href").is_none())
+        .map(|link| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: link.html(),
+            description: "Link is missing href attribute".to_string(),
+            location: page.locate(link),
+            rule: LinkHref::ID,
+            severity: Severity::Error,
+        })
+        .collect()
+}
+
+fn check_page_doesnt_have_captionless_table(page: &Page) -> CheckResult {
     let table_selector = Selector::parse("table").unwrap();
     let caption_selector = Selector::parse("caption").unwrap();
 
-    for table in document.select(&table_selector) {
-        if table.select(&caption_selector).next().is_none() {
-            return Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: "".to_string(),
-                description: "Page contains table without caption".to_string(),
-            });
-        };
-    }
-    Ok(())
+    page.document
+        .select(&table_selector)
+        .filter(|table| table.select(&caption_selector).next().is_none())
+        .map(|table| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: "".to_string(),
+            description: "Page contains table without caption".to_string(),
+            location: page.locate(table),
+            rule: TableCaption::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
-fn check_page_doesnt_have_captionless_figure(path: &Path, document: &Html) -> CheckResult {
+fn check_page_doesnt_have_captionless_figure(page: &Page) -> CheckResult {
     let fig_selector = Selector::parse("figure").unwrap();
     let figcap_selector = Selector::parse("figcaption").unwrap();
-    for fig in document.select(&fig_selector) {
-        if fig.select(&figcap_selector).next().is_none() {
-            return Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: "".to_string(),
-                description: "Page contains figure without caption".to_string(),
-            });
-        };
-    }
-    Ok(())
+
+    page.document
+        .select(&fig_selector)
+        .filter(|fig| fig.select(&figcap_selector).next().is_none())
+        .map(|fig| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: "".to_string(),
+            description: "Page contains figure without caption".to_string(),
+            location: page.locate(fig),
+            rule: FigureCaption::ID,
+            severity: Severity::Error,
+        })
+        .collect()
 }
-fn check_page_doesnt_have_labelless_form_elements(path: &Path, document: &Html) -> CheckResult {
+fn check_page_doesnt_have_labelless_form_elements(page: &Page) -> CheckResult {
     let form_selector = Selector::parse("form").unwrap();
     let input_selector = Selector::parse("input").unwrap();
     let label_selector = Selector::parse("label").unwrap();
 
-    for form in document.select(&form_selector) {
-        let input_ids = form
-            .select(&input_selector)
-            .filter_map(|input| input.value().id())
-            .collect::<HashSet<_>>();
+    let mut errors = vec![];
+    for form in page.document.select(&form_selector) {
         let label_ids = form
             .select(&label_selector)
             .filter_map(|input| input.value().attr("for"))
             .collect::<HashSet<_>>();
 
-        for id in input_ids {
-            if !label_ids.contains(id) {
-                return Err(CheckError::AccessibilityError {
-                    path: path.display().to_string(),
+        // walk the inputs in document order so the findings come out in a stable order
+        errors.extend(
+            form.select(&input_selector)
+                .filter_map(|input| input.value().id().map(|id| (input, id)))
+                .filter(|(_, id)| !label_ids.contains(id))
+                .map(|(input, id)| CheckError::AccessibilityError {
+                    path: page.path.display().to_string(),
                     offender: format!("id=\"{}\"", id),
                     description: "Form element without label attr".to_string(),
+                    location: page.locate(input),
+                    rule: FormLabel::ID,
+                    severity: Severity::Error,
+                }),
+        );
+    }
+    errors
+}
+fn check_page_doesnt_have_autoplay_media(page: &Page) -> CheckResult {
+    let tag_selector = Selector::parse("*").unwrap();
+
+    page.document
+        .select(&tag_selector)
+        .filter(|tag| tag.value().attr("autoplay").is_some())
+        .map(|tag| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: tag.html(),
+            description: "Page has media with autoplay enabled".to_string(),
+            location: page.locate(tag),
+            rule: NoAutoplay::ID,
+            severity: Severity::Error,
+        })
+        .collect()
+}
+
+fn check_color_contrast(page: &Page) -> CheckResult {
+    find_low_contrast(page)
+        .into_iter()
+        .map(|low| CheckError::AccessibilityError {
+            path: page.path.display().to_string(),
+            offender: format!(
+                "<{}> {} on {}",
+                low.element.value().name(),
+                low.color.to_hex(),
+                low.background.to_hex()
+            ),
+            description: format!(
+                "Text has a contrast ratio of {:.2}:1, below the minimum of {}:1",
+                low.ratio, low.minimum
+            ),
+            location: page.locate(low.element),
+            rule: ColorContrast::ID,
+            severity: Severity::Error,
+        })
+        .collect()
+}
+
+/// Elements whose attribute points at another file, as (selector, attribute).
+pub const LINKING_ELEMENTS: &[(&str, &str)] = &[
+    ("a[href]", "href"),
+    ("img[src]", "src"),
+    ("link[href]", "href"),
+    ("script[src]", "src"),
+];
+
+fn check_internal_links(page: &Page) -> CheckResult {
+    let site = match page.site {
+        Some(s) => s,
+        None => return vec![],
+    };
+
+    let mut errors = vec![];
+    for (selector, attr) in LINKING_ELEMENTS {
+        let selector = Selector::parse(selector).unwrap();
+        for elem in page.document.select(&selector) {
+            let url = elem.value().attr(attr).unwrap_or_default();
+            let link = match site.resolve(page.path, url) {
+                Some(l) => l,
+                None => continue,
+            };
+            if !link.target.is_file() {
+                errors.push(CheckError::ContentError {
+                    path: page.path.display().to_string(),
+                    offender: url.to_string(),
+                    description: "Link points to a file that doesn't exist".to_string(),
+                    location: page.locate(elem),
+                    rule: InternalLink::ID,
+                    severity: Severity::Error,
                 });
             }
         }
     }
-    Ok(())
+    errors.sort_by_key(|e| e.location());
+    errors
 }
-fn check_page_doesnt_have_autoplay_media(path: &Path, document: &Html) -> CheckResult {
-    let tag_selector = Selector::parse("*").unwrap();
 
-    for tag in document.select(&tag_selector) {
-        if tag.value().attr("autoplay").is_some() {
-            return Err(CheckError::AccessibilityError {
-                path: path.display().to_string(),
-                offender: tag.html(),
-                description: "Page has media with autoplay enabled".to_string(),
+fn check_internal_fragments(page: &Page) -> CheckResult {
+    let site = match page.site {
+        Some(s) => s,
+        None => return vec![],
+    };
+    let link_selector = Selector::parse("a[href]").unwrap();
+    let own_anchors = anchors(&page.document);
+
+    page.document
+        .select(&link_selector)
+        .filter_map(|elem| {
+            let url = elem.value().attr("href").unwrap_or_default();
+            let link = site.resolve(page.path, url)?;
+            let fragment = link.fragment?;
+            // `#` and `#top` scroll to the top of any page
+            if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") {
+                return None;
+            }
+            // links to files that aren't indexed pages are internal-link's problem
+            let target_anchors = if link.target == page.path {
+                &own_anchors
+            } else {
+                site.anchors(&link.target)?
+            };
+            if target_anchors.contains(&fragment) {
+                return None;
+            }
+            Some(CheckError::ContentError {
+                path: page.path.display().to_string(),
+                offender: url.to_string(),
+                description: format!(
+                    "Link points to anchor #{} which doesn't exist in {}",
+                    fragment,
+                    link.target.display()
+                ),
+                location: page.locate(elem),
+                rule: InternalFragment::ID,
+                severity: Severity::Error,
+            })
+        })
+        .collect()
+}
+
+fn check_spelling(page: &Page, conf: &SpellingConfig) -> CheckResult {
+    find_misspellings(page, conf)
+        .into_iter()
+        .map(|m| CheckError::ContentError {
+            path: page.path.display().to_string(),
+            offender: m.word,
+            description: format!("Unknown word in \"{}\"", m.sentence),
+            location: page.locate(m.element),
+            rule: Spelling::ID,
+            severity: Severity::Warning,
+        })
+        .collect()
+}
+
+fn check_doubled_words(page: &Page) -> CheckResult {
+    lazy_static! {
+        static ref RE_WORD: Regex = Regex::new(r"\p{L}+(?:['’]\p{L}+)*").unwrap();
+    }
+    let prose = Prose::of_page(page);
+
+    let mut errors = vec![];
+    let mut previous: Option<regex::Match> = None;
+    for word in RE_WORD.find_iter(&prose.text) {
+        let doubled = previous.is_some_and(|p| {
+            let between = &prose.text[p.end()..word.start()];
+            // a word ending one paragraph and starting the next isn't doubled
+            between.chars().all(char::is_whitespace)
+                && !between.contains("\n\n")
+                && p.as_str().to_lowercase() == word.as_str().to_lowercase()
+        });
+        previous = Some(word);
+        if !doubled {
+            continue;
+        }
+        if let Some(element) = prose.checked_element_at(word.start()) {
+            errors.push(CheckError::ContentError {
+                path: page.path.display().to_string(),
+                offender: word.as_str().to_string(),
+                description: format!(
+                    "Doubled word in \"{}\"",
+                    prose.sentence_around(word.start())
+                ),
+                location: page.locate(element),
+                rule: DoubledWord::ID,
+                severity: Severity::Warning,
             });
-        };
+        }
     }
-    Ok(())
+    errors
 }
 
-fn check_forbidden_tags(path: &Path, document: &Html) -> CheckResult {
-    let forbidden_tags = {
-        let mut set: HashSet<String> = HashSet::new();
-        set.insert("wip".to_string());
-        set
+fn check_readability(page: &Page, conf: &ReadabilityConfig) -> CheckResult {
+    let readability = match measure(page) {
+        Some(r) if r.words >= conf.min_words => r,
+        _ => return vec![],
     };
+    let root_selector = Selector::parse("main, article").unwrap();
+    let location = page
+        .document
+        .select(&root_selector)
+        .next()
+        .and_then(|root| page.locate(root));
+
+    let mut problems = vec![];
+    if let Some(ease) = readability.reading_ease {
+        if ease < conf.min_reading_ease {
+            let description = format!("Reading ease is below {}", conf.min_reading_ease);
+            problems.push((description, ease));
+        }
+    }
+    if readability.sentence_length > conf.max_sentence_length {
+        let description = format!(
+            "Sentences are longer than {} words on average",
+            conf.max_sentence_length
+        );
+        problems.push((description, readability.sentence_length));
+    }
+    if let Some(ratio) = readability.passive_ratio {
+        if ratio > conf.max_passive_ratio {
+            let description = format!(
+                "More than {} of sentences are passive",
+                conf.max_passive_ratio
+            );
+            problems.push((description, ratio));
+        }
+    }
 
-    // check for forbidden tags
-    let div_selector = Selector::parse("div").unwrap();
-    for div in document.select(&div_selector).filter(|elt: &ElementRef| {
-        elt.value()
-            .has_class("tags", CaseSensitivity::AsciiCaseInsensitive)
-    }) {
-        for elt in div
-            .children()
-            .filter_map(|child| match child.value().is_element() {
-                true => Some(child.value().as_element().unwrap()),
-                false => None,
-            })
-        {
-            if let Some(url) = elt.attr("href") {
-                let tag_name = extract_tag_name_from_url(url).unwrap_or_else(|| "".to_string());
+    problems
+        .into_iter()
+        .map(|(description, value)| CheckError::ContentError {
+            path: page.path.display().to_string(),
+            offender: format!("{:.2}", value),
+            description,
+            location,
+            rule: Readability::ID,
+            severity: Severity::Warning,
+        })
+        .collect()
+}
+
+/// Like publish dates, the tags of built pages are scraped from the links the theme
+/// renders for them, while markdown sources are checked against their front matter.
+fn check_forbidden_tags(page: &Page, forbidden_tags: &[String], tag_re: &Regex) -> CheckResult {
+    // tags are compared case insensitively, the config might say "WIP" while the url says "wip"
+    let forbidden_tags = forbidden_tags
+        .iter()
+        .map(|t| t.to_lowercase())
+        .collect::<HashSet<_>>();
 
-                if forbidden_tags.contains(&tag_name) {
-                    return Err(CheckError::ContentError {
-                        path: path.display().to_string(),
+    let mut errors = vec![];
+    let div_selector = Selector::parse("div").unwrap();
+    for div in page
+        .document
+        .select(&div_selector)
+        .filter(|elt: &ElementRef| {
+            elt.value()
+                .has_class("tags", CaseSensitivity::AsciiCaseInsensitive)
+        })
+    {
+        for elt in div.children().filter_map(ElementRef::wrap) {
+            if let Some(url) = elt.value().attr("href") {
+                let tag_name = extract_tag_name_from_url(url, tag_re).unwrap_or_default();
+
+                if forbidden_tags.contains(&tag_name.to_lowercase()) {
+                    errors.push(CheckError::ContentError {
+                        path: page.path.display().to_string(),
                         offender: tag_name,
                         description: "Forbidden tag".to_string(),
+                        location: page.locate(elt),
+                        rule: ForbiddenTag::ID,
+                        severity: Severity::Error,
                     });
                 }
             }
         }
     }
 
-    Ok(())
+    errors
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{ExternalLinksConfig, Generator, RuleLevel, DEFAULT_TAG_URL_PATTERN};
+    use std::collections::BTreeMap;
     use std::fs::{create_dir, File};
     use std::io::prelude::*;
     use std::path::PathBuf;
     use tempfile::TempDir;
 
+    fn loc(offset: usize, line: usize, column: usize) -> Option<Location> {
+        Some(Location {
+            offset,
+            line,
+            column,
+        })
+    }
+
     fn setup_test_config() -> ReaderConfig {
         ReaderConfig {
             root_dir: PathBuf::from("./public"),
             excluded_file_glob: None,
+            include_globs: vec![],
+            exclude_globs: vec![],
             forbidden_tags: vec!["WIP".to_string()],
             forbidden_folders: vec![
                 "unpublished".to_string(),
@@ -401,11 +961,27 @@ mod tests {
                 "<1950-01-01".to_string(),
                 ">today".to_string(),
             ],
+            date_rules: vec![
+                "0000-01-01".parse().unwrap(),
+                "<1950-01-01".parse().unwrap(),
+                ">today".parse().unwrap(),
+            ],
             pub_date_selector: Some("div.date".to_string()),
+            tag_url_pattern: DEFAULT_TAG_URL_PATTERN.to_string(),
+            tag_url_regex: Regex::new(DEFAULT_TAG_URL_PATTERN).unwrap(),
+            generator: Generator::Zola,
+            max_important: 10,
+            rules: BTreeMap::new(),
+            external_links: ExternalLinksConfig::default(),
+            spelling: SpellingConfig::default(),
+            style_rules: vec![],
+            readability: ReadabilityConfig::default(),
+            baseline_file: PathBuf::from(".proof-reader-baseline.json"),
+            cache_file: PathBuf::from(".proof-reader-cache.json"),
         }
     }
 
-    fn setup_test_wip_page() -> Html {
+    fn setup_test_wip_page() -> Page<'static> {
         let wip_page_contents = r#"
         <!DOCTYPE html>
         <html lang="en">
@@ -432,10 +1008,10 @@ mod tests {
         </html>
         "#;
 
-        Html::parse_document(wip_page_contents)
+        Page::parse(Path::new("wip.html"), wip_page_contents)
     }
 
-    fn setup_test_correct_page() -> Html {
+    fn setup_test_correct_page() -> Page<'static> {
         let wip_page_contents = r#"
         <!DOCTYPE html>
         <html lang="en">
@@ -447,7 +1023,7 @@ mod tests {
             <main>
                 <article>
                 <header>
-                    <h2>A "complete" web page</h2>
+                    <h1>A "complete" web page</h1>
                     <div class="date">Published: 2021-04-13</div>
                     <hr>
                 </header>
@@ -463,10 +1039,10 @@ mod tests {
         </html>
         "#;
 
-        Html::parse_document(wip_page_contents)
+        Page::parse(Path::new("wip.html"), wip_page_contents)
     }
 
-    fn setup_wrong_a11y_page() -> Html {
+    fn setup_wrong_a11y_page() -> Page<'static> {
         let wip_page_contents = r#"
         <!DOCTYPE html>
         <html>
@@ -518,45 +1094,88 @@ mod tests {
         </html>
         "#;
 
-        Html::parse_document(wip_page_contents)
+        Page::parse(Path::new("wip.html"), wip_page_contents)
     }
 
     #[test]
     fn test_discovers_forbidden_pub_date() -> Result<(), String> {
-        let test_doc = setup_test_wip_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_test_wip_page();
 
         let conf = setup_test_config();
 
         let res = check_for_invalid_publish_dates(
-            test_path,
-            &test_doc,
+            &test_page,
             &conf.pub_date_selector.unwrap(),
-            &conf.forbidden_dates,
+            &conf.date_rules,
         );
 
-        let expected_err = Err(CheckError::ContentError {
+        let expected_err = vec![CheckError::ContentError {
             path: "wip.html".to_string(),
             offender: "0000-01-01".to_string(),
             description: "Forbidden publish date".to_string(),
-        });
+            location: loc(302, 13, 21),
+            rule: "forbidden-date",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
     }
 
+    #[test]
+    fn test_discovers_future_and_ranged_pub_dates() -> Result<(), String> {
+        let next_month = "today+1m"
+            .parse::<crate::date::DateExpr>()?
+            .resolve(today())
+            .unwrap();
+        let contents = format!(
+            r#"<html><body><div class="date">Published: {}</div></body></html>"#,
+            next_month.format("%Y-%m-%d")
+        );
+        let test_page = Page::parse(Path::new("future.html"), &contents);
+        let conf = setup_test_config();
+
+        let res = check_for_invalid_publish_dates(
+            &test_page,
+            conf.pub_date_selector.as_ref().unwrap(),
+            &conf.date_rules,
+        );
+
+        let expected_err = vec![CheckError::ContentError {
+            path: "future.html".to_string(),
+            offender: next_month.format("%Y-%m-%d").to_string(),
+            description: "Forbidden publish date".to_string(),
+            location: loc(12, 1, 13),
+            rule: "forbidden-date",
+            severity: Severity::Error,
+        }];
+        assert_eq!(res, expected_err);
+
+        let res = check_for_invalid_publish_dates(
+            &test_page,
+            conf.pub_date_selector.as_ref().unwrap(),
+            &["today..today+7d".parse()?],
+        );
+        assert!(res.is_empty(), "{:?}", res);
+        Ok(())
+    }
+
     #[test]
     fn test_discovers_forbidden_tag() -> Result<(), String> {
-        let test_doc = setup_test_wip_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_test_wip_page();
+
+        let conf = setup_test_config();
 
-        let res = check_forbidden_tags(test_path, &test_doc);
+        let res = check_forbidden_tags(&test_page, &conf.forbidden_tags, &conf.tag_url_regex);
 
-        let expected_err = Err(CheckError::ContentError {
+        let expected_err = vec![CheckError::ContentError {
             path: "wip.html".to_string(),
             offender: "wip".to_string(),
             description: "Forbidden tag".to_string(),
-        });
+            location: loc(531, 20, 35),
+            rule: "forbidden-tag",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -571,15 +1190,67 @@ mod tests {
         let forbidden_file_path = unpublished_dir.join("fobidden.html");
         File::create(&forbidden_file_path).expect("failed to create file");
 
-        let res = check_for_forbidden_files(&forbidden_file_path);
+        let conf = setup_test_config();
 
-        let expected_err = Err(CheckError::ForbiddenFile {
+        let res = check_for_forbidden_files(&forbidden_file_path, &conf.forbidden_folders);
+
+        let expected_err = vec![CheckError::ForbiddenFile {
             path: test_dir
                 .path()
                 .join("unpublished/fobidden.html")
                 .display()
                 .to_string(),
-        });
+            rule: "forbidden-folder",
+            severity: Severity::Error,
+        }];
+
+        assert_eq!(res, expected_err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_discovers_configured_forbidden_folder() -> Result<(), String> {
+        let conf = setup_test_config();
+        let test_path = Path::new("public/editing-queue/post.html");
+
+        let res = check_for_forbidden_files(test_path, &conf.forbidden_folders);
+
+        let expected_err = vec![CheckError::ForbiddenFile {
+            path: "public/editing-queue/post.html".to_string(),
+            rule: "forbidden-folder",
+            severity: Severity::Error,
+        }];
+
+        assert_eq!(res, expected_err);
+        assert!(check_for_forbidden_files(
+            Path::new("public/posts/post.html"),
+            &conf.forbidden_folders
+        )
+        .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_discovers_forbidden_tag_with_custom_pattern() -> Result<(), String> {
+        let test_page = Page::parse(
+            Path::new("draft.html"),
+            r#"<html><body><div class="tags"><a href="/categories/Draft/">Draft</a></div></body></html>"#,
+        );
+
+        let res = check_forbidden_tags(
+            &test_page,
+            &["draft".to_string()],
+            &Regex::new("categories/([^/]+)").unwrap(),
+        );
+
+        let expected_err = vec![CheckError::ContentError {
+            path: "draft.html".to_string(),
+            offender: "Draft".to_string(),
+            description: "Forbidden tag".to_string(),
+            location: loc(30, 1, 31),
+            rule: "forbidden-tag",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -587,32 +1258,196 @@ mod tests {
 
     #[test]
     fn test_correct_file_passes() -> Result<(), String> {
-        let test_doc = setup_test_correct_page();
+        let test_page = setup_test_correct_page();
         let test_dir = TempDir::new().expect("could not create temp dir");
         let page_path = test_dir.path().join("page.html");
         let mut f = File::create(&page_path).expect("failed to create file");
-        f.write_all(test_doc.root_element().html().as_bytes())
+        f.write_all(test_page.document.root_element().html().as_bytes())
             .expect("failed to write file contents");
 
         let conf = setup_test_config();
 
-        let res = check_html_file(&page_path, &conf);
-        assert!(res.is_ok(), "{:?}", res);
+        let res = check_html_file(&page_path, &conf, None);
+        assert!(res.is_empty(), "{:?}", res);
         Ok(())
     }
 
+    #[test]
+    fn test_reports_every_violation_in_file() -> Result<(), String> {
+        let test_page = setup_wrong_a11y_page();
+        let test_dir = TempDir::new().expect("could not create temp dir");
+        let page_path = test_dir.path().join("page.html");
+        let mut f = File::create(&page_path).expect("failed to create file");
+        f.write_all(test_page.document.root_element().html().as_bytes())
+            .expect("failed to write file contents");
+
+        let conf = setup_test_config();
+
+        let res = check_html_file(&page_path, &conf, None);
+        // one finding for each of the fifteen broken things in the page
+        assert_eq!(res.len(), 15, "{:#?}", res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_levels_are_applied() -> Result<(), String> {
+        let test_page = setup_wrong_a11y_page();
+        let test_dir = TempDir::new().expect("could not create temp dir");
+        let page_path = test_dir.path().join("page.html");
+        let mut f = File::create(&page_path).expect("failed to create file");
+        f.write_all(test_page.document.root_element().html().as_bytes())
+            .expect("failed to write file contents");
+
+        let mut conf = setup_test_config();
+        conf.rules.insert("img-alt".to_string(), RuleLevel::Off);
+        conf.rules.insert("single-h1".to_string(), RuleLevel::Warn);
+
+        let res = check_html_file(&page_path, &conf, None);
+
+        assert_eq!(res.len(), 14, "{:#?}", res);
+        let warnings = res
+            .iter()
+            .filter(|e| e.severity() == Severity::Warning)
+            .collect::<Vec<_>>();
+        assert_eq!(warnings.len(), 1, "{:#?}", warnings);
+        assert!(warnings
+            .iter()
+            .any(|e| e.rule() == "single-h1" && e.offender() == "<h1>Second title</h1>"));
+        // no-title-attr still fails the run unless the config says otherwise
+        assert!(res
+            .iter()
+            .any(|e| e.rule() == "no-title-attr" && e.severity() == Severity::Error));
+        Ok(())
+    }
+
+    #[test]
+    fn test_discovers_every_img_without_alt() -> Result<(), String> {
+        let test_page = Page::parse(
+            Path::new("imgs.html"),
+            r#"<html><body><img src="a.jpg"><img src="b.jpg" alt="b"><img src="c.jpg"></body></html>"#,
+        );
+
+        let res = check_img_tags_have_alts(&test_page);
+
+        let expected_err = vec![
+            CheckError::AccessibilityError {
+                path: "imgs.html".to_string(),
+                offender: "<img src=\"a.jpg\">".to_string(),
+                description: "Image tag without alt".to_string(),
+                location: loc(12, 1, 13),
+                rule: "img-alt",
+                severity: Severity::Error,
+            },
+            CheckError::AccessibilityError {
+                path: "imgs.html".to_string(),
+                offender: "<img src=\"c.jpg\">".to_string(),
+                description: "Image tag without alt".to_string(),
+                location: loc(54, 1, 55),
+                rule: "img-alt",
+                severity: Severity::Error,
+            },
+        ];
+
+        assert_eq!(res, expected_err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_discovers_broken_internal_links() {
+        let site_dir = TempDir::new().expect("could not create temp dir");
+        create_dir(site_dir.path().join("about")).expect("failed to create folder");
+        for file in &["about/index.html", "style.css", "logo.png"] {
+            File::create(site_dir.path().join(file)).expect("failed to create file");
+        }
+        let site = Site::index(site_dir.path());
+        let page_path = site_dir.path().join("index.html");
+        let mut test_page = Page::parse(
+            &page_path,
+            r#"<html><head><link rel="stylesheet" href="/style.css"><script src="app.js"></script></head>
+<body><a href="about/">About</a> <a href="/about">About</a> <a href="/contact/">Contact</a>
+<a href="https://example.com/missing">elsewhere</a> <img src="./logo.png" alt=""> <img src="../logo.jpg" alt="">
+</body></html>"#,
+        );
+        test_page.site = Some(&site);
+
+        let res = check_internal_links(&test_page);
+
+        assert_eq!(
+            res.iter().map(|e| e.offender()).collect::<Vec<_>>(),
+            vec!["app.js", "/contact/", "../logo.jpg"]
+        );
+        assert_eq!(res[1].location().map(|l| (l.line, l.column)), Some((2, 61)));
+    }
+
+    #[test]
+    fn test_links_are_not_checked_without_site() {
+        let test_page = setup_wrong_a11y_page();
+
+        assert!(check_internal_links(&test_page).is_empty());
+        assert!(check_internal_fragments(&test_page).is_empty());
+    }
+
+    #[test]
+    fn test_discovers_missing_fragments() {
+        let site_dir = TempDir::new().expect("could not create temp dir");
+        create_dir(site_dir.path().join("posts")).expect("failed to create folder");
+        let mut f =
+            File::create(site_dir.path().join("posts/foo.html")).expect("failed to create file");
+        f.write_all(br#"<html><body><h2 id="installation">Installation</h2></body></html>"#)
+            .expect("failed to write file contents");
+        let site = Site::index(site_dir.path());
+        let page_path = site_dir.path().join("index.html");
+        let mut test_page = Page::parse(
+            &page_path,
+            r##"<html><body><h1 id="intro">Intro</h1>
+<a href="#intro">ok</a> <a href="#outro">missing</a> <a href="#">top</a>
+<a href="posts/foo.html#installation">ok</a> <a href="/posts/foo.html#usage">missing</a>
+<a href="/nowhere.html#usage">not a page</a> <a href="https://example.com/#usage">external</a>
+</body></html>"##,
+        );
+        test_page.site = Some(&site);
+
+        let res = check_internal_fragments(&test_page);
+
+        assert_eq!(
+            res.iter().map(|e| e.offender()).collect::<Vec<_>>(),
+            vec!["#outro", "/posts/foo.html#usage"]
+        );
+        assert!(res[1]
+            .description()
+            .starts_with("Link points to anchor #usage"));
+        assert_eq!(res[1].path(), page_path.display().to_string());
+    }
+
+    #[test]
+    fn test_rule_ids_are_unique_kebab_case() {
+        let mut seen = HashSet::new();
+        for rule in rules() {
+            assert!(seen.insert(rule.id()), "duplicate rule id {}", rule.id());
+            assert!(
+                rule.id()
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+                "rule id {} is not kebab-case",
+                rule.id()
+            );
+        }
+    }
+
     #[test]
     fn test_discovers_img_without_alt() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_img_tags_have_alts(test_path, &test_doc);
+        let res = check_img_tags_have_alts(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<img src=\"img_girl.jpg\">".to_string(),
             description: "Image tag without alt".to_string(),
-        });
+            location: loc(733, 21, 17),
+            rule: "img-alt",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -620,16 +1455,18 @@ mod tests {
 
     #[test]
     fn test_discovers_tag_with_title_attr() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_tags_dont_have_title_attr(test_path, &test_doc);
+        let res = check_tags_dont_have_title_attr(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<a title=\"a useless link\"></a>".to_string(),
             description: "Tag in page has title attr".to_string(),
-        });
+            location: loc(775, 22, 17),
+            rule: "no-title-attr",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -637,16 +1474,18 @@ mod tests {
 
     #[test]
     fn test_discovers_disabled_zoom() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_disabe_zoom(test_path, &test_doc);
+        let res = check_page_doesnt_disabe_zoom(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "".to_string(),
             description: "Page disables zoom".to_string(),
-        });
+            location: loc(102, 6, 13),
+            rule: "no-disabled-zoom",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -654,16 +1493,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_without_title() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_has_title(test_path, &test_doc);
+        let res = check_page_has_title(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "".to_string(),
             description: "Page is missing a title tag".to_string(),
-        });
+            location: loc(48, 4, 9),
+            rule: "page-title",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -671,16 +1512,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_without_lang_attr() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_has_lang_attr(test_path, &test_doc);
+        let res = check_page_has_lang_attr(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "".to_string(),
             description: "Page doesn't have lang attribute".to_string(),
-        });
+            location: loc(33, 3, 9),
+            rule: "html-lang",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -688,16 +1531,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_positive_tabindex() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_positive_tabindex(test_path, &test_doc);
+        let res = check_page_doesnt_have_positive_tabindex(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<div tabindex=\"24\"></div>".to_string(),
             description: "Page has tag with prositive tab index".to_string(),
-        });
+            location: loc(822, 23, 17),
+            rule: "no-positive-tabindex",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -705,16 +1550,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_autofocus_attr() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_autofocus(test_path, &test_doc);
+        let res = check_page_doesnt_have_autofocus(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<input autofocus=\"\">".to_string(),
             description: "Tag in page has autofocus attr".to_string(),
-        });
+            location: loc(913, 25, 17),
+            rule: "no-autofocus",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -722,33 +1569,129 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_multiple_h1_elements() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_multiple_h1_elements(test_path, &test_doc);
+        let res = check_page_doesnt_have_multiple_h1_elements(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<h1>Second title</h1>".to_string(),
             description: "Has multiple h1 headings".to_string(),
-        });
+            location: loc(399, 13, 17),
+            rule: "single-h1",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
     }
 
+    #[test]
+    fn test_discovers_heading_hierarchy_problems() {
+        let test_page = setup_wrong_a11y_page();
+
+        let res = check_heading_hierarchy(&test_page)
+            .iter()
+            .map(|e| (e.offender().to_string(), e.description()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            res,
+            vec![
+                (
+                    "<h3> a h3 that's way before it's time".to_string(),
+                    "Page starts with an h3 instead of an h1".to_string()
+                ),
+                (
+                    "<h3> A \"complete\" web page".to_string(),
+                    "Heading skips from h1 to h3".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discovers_empty_headings() {
+        let test_page = Page::parse(
+            Path::new("page.html"),
+            r#"<h1>Title</h1><h2> </h2><h2><img src="logo.png" alt="Logo"></h2><h2 aria-label="Menu"></h2><h4></h4>"#,
+        );
+
+        let res = check_heading_hierarchy(&test_page)
+            .iter()
+            .map(|e| (e.offender().to_string(), e.description()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            res,
+            vec![
+                ("<h2>".to_string(), "Heading is empty".to_string()),
+                (
+                    "<h4>".to_string(),
+                    "Heading skips from h2 to h4".to_string()
+                ),
+                ("<h4>".to_string(), "Heading is empty".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discovers_doubled_words() {
+        let test_page = Page::parse(
+            Path::new("page.html"),
+            "<main><h2>Setup</h2><p>Setup is is easy, <em>the</em>\n the <code>x x</code> rest had had its say.</p></main>",
+        );
+
+        let res = check_doubled_words(&test_page)
+            .iter()
+            .map(|e| e.offender().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(res, vec!["is", "the", "had"]);
+    }
+
+    #[test]
+    fn test_discovers_hard_to_read_pages() {
+        let test_page = Page::parse(
+            Path::new("page.html"),
+            r#"<html lang="en"><body><main><p>The considerably complicated documentation was
+            comprehensively reorganized by the editorial department in preparation for
+            the upcoming international conference.</p></main></body></html>"#,
+        );
+        let conf = ReadabilityConfig {
+            min_words: 0,
+            ..ReadabilityConfig::default()
+        };
+
+        let res = check_readability(&test_page, &conf)
+            .iter()
+            .map(|e| e.description())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            res,
+            vec![
+                "Reading ease is below 30",
+                "More than 0.25 of sentences are passive",
+            ]
+        );
+        assert!(check_readability(&test_page, &ReadabilityConfig::default()).is_empty());
+    }
+
     #[test]
     fn test_discovers_page_with_hrefless_link() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_hrefless_link(test_path, &test_doc);
+        let res = check_page_doesnt_have_hrefless_link(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<a title=\"a useless link\"></a>".to_string(),
             description: "Link is missing href attribute".to_string(),
-        });
+            location: loc(775, 22, 17),
+            rule: "link-href",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -756,16 +1699,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_captionless_table() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_captionless_table(test_path, &test_doc);
+        let res = check_page_doesnt_have_captionless_table(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "".to_string(),
             description: "Page contains table without caption".to_string(),
-        });
+            location: loc(1193, 31, 17),
+            rule: "table-caption",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -773,16 +1718,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_captionless_figure() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_captionless_figure(test_path, &test_doc);
+        let res = check_page_doesnt_have_captionless_figure(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "".to_string(),
             description: "Page contains figure without caption".to_string(),
-        });
+            location: loc(1066, 28, 17),
+            rule: "figure-caption",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -790,16 +1737,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_labelless_form() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_labelless_form_elements(test_path, &test_doc);
+        let res = check_page_doesnt_have_labelless_form_elements(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "id=\"other\"".to_string(),
             description: "Form element without label attr".to_string(),
-        });
+            location: loc(955, 26, 17),
+            rule: "form-label",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
@@ -807,16 +1756,18 @@ mod tests {
 
     #[test]
     fn test_discovers_page_with_autoplay_media() -> Result<(), String> {
-        let test_doc = setup_wrong_a11y_page();
-        let test_path = Path::new("wip.html");
+        let test_page = setup_wrong_a11y_page();
 
-        let res = check_page_doesnt_have_autoplay_media(test_path, &test_doc);
+        let res = check_page_doesnt_have_autoplay_media(&test_page);
 
-        let expected_err = Err(CheckError::AccessibilityError {
+        let expected_err = vec![CheckError::AccessibilityError {
             path: "wip.html".to_string(),
             offender: "<audio autoplay=\"\"></audio>".to_string(),
             description: "Page has media with autoplay enabled".to_string(),
-        });
+            location: loc(1456, 41, 17),
+            rule: "no-autoplay",
+            severity: Severity::Error,
+        }];
 
         assert_eq!(res, expected_err);
         Ok(())
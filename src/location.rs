@@ -0,0 +1,271 @@
+use ego_tree::NodeId;
+use scraper::{ElementRef, Html};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Elements whose contents are text rather than markup, so any `<` in them isn't a tag.
+/// scraper parses with scripting enabled, which makes `noscript` one of them.
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes", "noscript",
+];
+
+/// Where an element starts in the original source of a file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Location {
+    /// Byte offset of the `<` of the start tag.
+    pub offset: usize,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in characters.
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Maps the elements of a parsed document back to their start tags in the source.
+///
+/// scraper doesn't keep track of source positions, so instead the start tags are
+/// scanned from the source and the n-th element with a given name in document order
+/// is matched to the n-th start tag with that name. Elements the parser inserted
+/// on its own (like an implied `tbody`) have no start tag and thus no location.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    line_starts: Vec<usize>,
+    elements: HashMap<NodeId, Location>,
+}
+
+impl SourceMap {
+    /// A map that only knows about lines, for files that aren't HTML.
+    pub fn for_text(source: &str) -> SourceMap {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        SourceMap {
+            line_starts,
+            elements: HashMap::new(),
+        }
+    }
+
+    pub fn new(source: &str, document: &Html) -> SourceMap {
+        let mut tags: HashMap<String, Vec<usize>> = HashMap::new();
+        for (name, offset) in scan_start_tags(source) {
+            tags.entry(name).or_default().push(offset);
+        }
+
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut map = SourceMap::for_text(source);
+        for node in document.tree.root().descendants() {
+            let element = match ElementRef::wrap(node) {
+                Some(e) => e,
+                None => continue,
+            };
+            let name = element.value().name().to_ascii_lowercase();
+            let ordinal = seen.get(&name).copied().unwrap_or(0);
+            let offset = match tags.get(&name).and_then(|t| t.get(ordinal)) {
+                Some(&o) => o,
+                None => continue,
+            };
+            // an element the parser inserted would take the start tag of a later one,
+            // but a start tag always comes before the one of its first child
+            let inserted = element
+                .children()
+                .find_map(ElementRef::wrap)
+                .and_then(|child| {
+                    let child_name = child.value().name().to_ascii_lowercase();
+                    let skip = usize::from(child_name == name);
+                    let child_ordinal = seen.get(&child_name).copied().unwrap_or(0) + skip;
+                    tags.get(&child_name)?.get(child_ordinal).copied()
+                })
+                .is_some_and(|child_offset| child_offset < offset);
+            if inserted {
+                continue;
+            }
+            map.elements
+                .insert(element.id(), map.location_of_offset(source, offset));
+            seen.insert(name, ordinal + 1);
+        }
+        map
+    }
+
+    pub fn locate(&self, element: ElementRef) -> Option<Location> {
+        self.elements.get(&element.id()).copied()
+    }
+
+    pub fn location_of_offset(&self, source: &str, offset: usize) -> Location {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = source
+            .get(line_start..offset)
+            .map_or(0, |s| s.chars().count());
+        Location {
+            offset,
+            line: line + 1,
+            column: column + 1,
+        }
+    }
+}
+
+/// Finds the name and byte offset of every start tag in `source`,
+/// skipping comments, doctypes, end tags and the contents of raw text elements.
+fn scan_start_tags(source: &str) -> Vec<(String, usize)> {
+    let mut tags = vec![];
+    let mut pos = 0;
+
+    while let Some(found) = source[pos..].find('<') {
+        let start = pos + found;
+        let rest = &source[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            pos = comment
+                .find("-->")
+                .map_or(source.len(), |end| start + 4 + end + 3);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") || rest.starts_with("</") {
+            pos = rest.find('>').map_or(source.len(), |end| start + end + 1);
+            continue;
+        }
+
+        let name = rest[1..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == ':')
+            .collect::<String>();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            pos = start + 1;
+            continue;
+        }
+        let name = name.to_ascii_lowercase();
+        pos = start + 1 + name.len() + tag_end(&rest[1 + name.len()..]);
+
+        if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            let close = format!("</{}", name);
+            pos = find_ignore_ascii_case(&source[pos..], &close)
+                .map_or(source.len(), |end| pos + end);
+        }
+        tags.push((name, start));
+    }
+    tags
+}
+
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.match_indices('<').map(|(i, _)| i).find(|&i| {
+        haystack
+            .get(i..i + needle.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(needle))
+    })
+}
+
+/// Length of the rest of a start tag, up to and including the `>`,
+/// not counting any `>` inside quoted attribute values.
+fn tag_end(rest: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return i + 1,
+            _ => (),
+        }
+    }
+    rest.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::Selector;
+
+    fn locate_all(source: &str, selector: &str) -> Vec<Option<Location>> {
+        let document = Html::parse_document(source);
+        let map = SourceMap::new(source, &document);
+        let selector = Selector::parse(selector).unwrap();
+        document.select(&selector).map(|e| map.locate(e)).collect()
+    }
+
+    #[test]
+    fn test_locates_elements() {
+        let source = "<html>\n<body>\n  <p>one</p>\n  <p>two <img src=x></p>\n</body></html>";
+
+        let ps = locate_all(source, "p");
+        assert_eq!(
+            ps,
+            vec![
+                Some(Location {
+                    offset: 16,
+                    line: 3,
+                    column: 3
+                }),
+                Some(Location {
+                    offset: 29,
+                    line: 4,
+                    column: 3
+                }),
+            ]
+        );
+        assert_eq!(&source[ps[1].unwrap().offset..][..2], "<p");
+
+        let img = locate_all(source, "img")[0].unwrap();
+        assert_eq!((img.line, img.column), (4, 10));
+    }
+
+    #[test]
+    fn test_skips_comments_and_raw_text() {
+        let source = r#"<html><head><title>a <p> b</title>
+<script>if (a <p) { document.write("<p>") }</script></head>
+<!-- <p>commented out</p> -->
+<body><p title="a > b">real</p></body></html>"#;
+
+        let p = locate_all(source, "p");
+        assert_eq!(p.len(), 1);
+        assert_eq!(p[0].map(|l| (l.line, l.column)), Some((4, 7)));
+    }
+
+    #[test]
+    fn test_implied_elements_have_no_location() {
+        let source = "<p>no html, head or body tags</p>";
+
+        assert_eq!(locate_all(source, "body"), vec![None]);
+        assert_eq!(locate_all(source, "p")[0].map(|l| l.offset), Some(0));
+    }
+
+    #[test]
+    fn test_noscript_contents_are_not_tags() {
+        let source = r#"<html><body>
+<noscript><img src="tracker.gif"></noscript>
+<p>text</p>
+<img src="photo.jpg">
+</body></html>"#;
+
+        let img = locate_all(source, "img");
+        assert_eq!(img.len(), 1);
+        assert_eq!(img[0].map(|l| (l.line, l.column)), Some((4, 1)));
+    }
+
+    #[test]
+    fn test_inserted_elements_dont_take_later_tags() {
+        let source =
+            "<table><tr><td>a</td></tr></table>\n<table><tbody><tr><td>b</td></tr></tbody></table>";
+
+        let tbodies = locate_all(source, "tbody");
+        assert_eq!(tbodies[0], None);
+        assert_eq!(tbodies[1].map(|l| (l.line, l.column)), Some((2, 8)));
+        let trs = locate_all(source, "tr");
+        assert_eq!(trs[1].map(|l| (l.line, l.column)), Some((2, 15)));
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let source = "<html><body><p>héllo wörld <em>x</em></p></body></html>";
+        let em = locate_all(source, "em")[0].unwrap();
+
+        assert_eq!(&source[em.offset..][..3], "<em");
+        assert_eq!(em.column, 28);
+    }
+}
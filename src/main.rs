@@ -1,47 +1,216 @@
+mod baseline;
+mod cache;
+mod changed;
+#[macro_use]
+mod check;
 mod cli;
 mod config;
+mod contrast;
+mod css;
 mod date;
 mod dispatch;
 mod error;
+#[cfg(feature = "external-links")]
+mod external;
+mod frontmatter;
 mod html;
+mod location;
+mod markdown;
+mod prose;
+mod readability;
+mod report;
+mod site;
+mod spelling;
+mod style;
+mod suppress;
+mod walk;
+mod watch;
 
 use rayon::prelude::*;
-use walkdir::WalkDir;
 
+use crate::baseline::Baseline;
+use crate::cache::Cache;
+use crate::changed::{affected_files, changed_since, Scope};
 use crate::cli::*;
 use crate::config::{build_config, ReaderConfig};
 use crate::dispatch::*;
-use crate::error::CheckError;
+use crate::error::{CheckError, Severity};
+use crate::readability::{measure_file, Readability};
+use crate::report::{write_findings, write_readability_table, Format};
+use crate::site::Site;
+use crate::walk::{files_to_check, FileFilter};
 
-use std::path::Path;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+fn print_rules() {
+    for rule in rules() {
+        println!(
+            "{:<22} {:<14} {:<8} {}",
+            rule.id, rule.category, rule.default_severity, rule.name
+        );
+    }
+}
+
+/// The readability of every HTML page with prose, sorted by path.
+fn readability_table(files: &[PathBuf]) -> Vec<(String, Readability)> {
+    let mut table = files
+        .par_iter()
+        .filter(|path| path.extension().is_some_and(|e| e == "html"))
+        .filter_map(|path| Some((path.display().to_string(), measure_file(path)?)))
+        .collect::<Vec<_>>();
+    table.sort_by(|a, b| a.0.cmp(&b.0));
+    table
+}
 
 fn main() -> Result<(), String> {
     let matches = build_cli().get_matches();
 
-    let conf: ReaderConfig = matches
-        .value_of("config")
-        .map_or_else(ReaderConfig::default, |p| {
-            build_config(Some(Path::new(p))).unwrap()
-        });
-
-    let (_, errors): (Vec<_>, Vec<_>) = WalkDir::new(&conf.root_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .collect::<Vec<_>>()
-        .into_par_iter()
-        .map(|e| check_file(e.path(), &conf))
-        .partition(Result::is_ok);
-
-    for err in &errors {
-        match err {
-            Ok(_) => (),
-            Err(e) => println!("{}", e),
+    if matches.is_present("list-rules") {
+        print_rules();
+        return Ok(());
+    }
+
+    let format: Format = matches.value_of("format").unwrap_or("human").parse()?;
+    if matches.is_present("readability") && format != Format::Json {
+        return Err("--readability needs --format json".to_string());
+    }
+
+    let mut conf: ReaderConfig =
+        build_config(matches.value_of("config").map(Path::new)).map_err(|e| e.to_string())?;
+
+    // an explicitly passed root takes precedence over the one in the config file
+    if matches.occurrences_of("root") > 0 {
+        if let Some(root) = matches.value_of("root") {
+            conf.root_dir = PathBuf::from(root);
         }
     }
 
-    if errors.is_empty() {
+    let mut excludes = conf.excludes();
+    if let Some(globs) = matches.values_of("exclude") {
+        excludes.extend(globs.map(String::from));
+    }
+    let filter = FileFilter::new(&conf.include_globs, &excludes)
+        .map_err(|e| format!("Invalid --exclude: {}", e))?;
+
+    if matches.is_present("watch") {
+        return watch::watch(&conf, &filter);
+    }
+
+    let files = files_to_check(&conf.root_dir, &filter);
+    let changed = match matches.value_of("changed-since") {
+        Some(rev) => match affected_files(&changed_since(rev)?, &conf) {
+            Scope::Files(changed) => Some(changed),
+            Scope::Everything(reason) => {
+                eprintln!("Checking every file, {}", reason);
+                None
+            }
+        },
+        None => None,
+    };
+    // the files that get every check, the others only get the site-wide ones. These
+    // are kept in order for the reports, `changed` is a set so it's used for lookups.
+    let is_checked = |path: &PathBuf| changed.as_ref().is_none_or(|c| c.contains(path));
+    let checked = match &changed {
+        Some(changed) => files
+            .iter()
+            .filter(|path| changed.contains(*path))
+            .cloned()
+            .collect(),
+        None => files.clone(),
+    };
+    let site_wide_conf = changed.as_ref().map(|_| site_wide_config(&conf));
+
+    let cache = Some(&conf)
+        .filter(|_| !matches.is_present("no-cache"))
+        .map(|conf| Cache::load(&conf.cache_file, conf));
+    let site = match &cache {
+        Some(cache) => Site::index_with(&conf.root_dir, |path, contents| {
+            cache.anchors(path, contents)
+        }),
+        None => Site::index(&conf.root_dir),
+    };
+    let mut errors: Vec<CheckError> = files
+        .par_iter()
+        .flat_map(|path| match (&cache, &site_wide_conf) {
+            (Some(cache), Some(site_wide_conf)) if !is_checked(path) => {
+                match cache.cached(path, &site) {
+                    Some(findings) => findings
+                        .into_iter()
+                        .filter(|f| SITE_WIDE_RULES.contains(&f.rule()))
+                        .collect(),
+                    None => check_file(path, site_wide_conf, &site),
+                }
+            }
+            (None, Some(site_wide_conf)) if !is_checked(path) => {
+                check_file(path, site_wide_conf, &site)
+            }
+            (Some(cache), _) => cache.check(path, &site, || check_file(path, &conf, &site)),
+            (None, _) => check_file(path, &conf, &site),
+        })
+        .collect();
+    if let Some(cache) = &cache {
+        cache.index_pages(&site);
+        // a cache that can't be written only makes the next run slower
+        if let Err(e) = cache.save() {
+            eprintln!("Could not write cache {}: {}", conf.cache_file.display(), e);
+        }
+    }
+
+    #[cfg(feature = "external-links")]
+    if matches.is_present("external-links") {
+        let timeout = std::time::Duration::from_secs(conf.external_links.timeout_secs);
+        let client = external::UreqClient::new(timeout);
+        errors.extend(external::check_external_links(&checked, &conf, &client));
+    }
+
+    let baseline_path = matches
+        .value_of("baseline")
+        .map_or_else(|| conf.baseline_file.clone(), PathBuf::from);
+    if matches.is_present("write-baseline") {
+        let baseline = Baseline::of_findings(&errors, &conf.root_dir);
+        baseline.save(&baseline_path).map_err(|e| {
+            format!(
+                "Could not write baseline {}: {}",
+                baseline_path.display(),
+                e
+            )
+        })?;
+        println!(
+            "Wrote {} findings to {}",
+            baseline.len(),
+            baseline_path.display()
+        );
+        return Ok(());
+    }
+    if let Some(baseline) = Baseline::load(&baseline_path)? {
+        errors = baseline.apply(errors, &checked, &conf);
+    }
+
+    let out = stdout();
+    let mut out = out.lock();
+    write_findings(format, &errors, &mut out)
+        .and_then(|_| {
+            if matches.is_present("readability") {
+                write_readability_table(&readability_table(&checked), &mut out)
+            } else {
+                Ok(())
+            }
+        })
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("Could not write report: {}", e))?;
+
+    let (failures, warnings): (Vec<_>, Vec<_>) =
+        errors.iter().partition(|e| e.severity() == Severity::Error);
+
+    // machine readable formats need stdout to themselves
+    if format == Format::Human && !warnings.is_empty() {
+        println!("{} Checks produced warnings", warnings.len());
+    }
+
+    if failures.is_empty() {
         Ok(())
     } else {
-        Err(format!("{} Checks failed!", errors.len()))
+        Err(format!("{} Checks failed!", failures.len()))
     }
 }
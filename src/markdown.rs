@@ -0,0 +1,559 @@
+use pulldown_cmark::{html::push_html, Event, Options, Parser, Tag};
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::check::{run_checks, Rule};
+use crate::date::today;
+use crate::dispatch::RuleInfo;
+use crate::error::{Category, CheckError, Severity};
+use crate::frontmatter::{self, Block, FrontMatter};
+use crate::html::{
+    self, ForbiddenDate, ForbiddenFolder, ForbiddenTag, HeadingOrder, ImgAlt, InternalLink, Page,
+    PageTitle,
+};
+use crate::location::{Location, SourceMap};
+use crate::site::Site;
+use crate::suppress::apply_suppressions;
+use crate::ReaderConfig;
+
+pub const DRAFT: RuleInfo = RuleInfo {
+    id: "draft",
+    name: "Page isn't marked as a draft",
+    category: Category::Content,
+    default_severity: Severity::Error,
+};
+
+pub const FRONT_MATTER: RuleInfo = RuleInfo {
+    id: "front-matter",
+    name: "Front matter can be parsed",
+    category: Category::Content,
+    default_severity: Severity::Error,
+};
+
+/// The html rules that are run on the rendered markdown.
+const RENDERED_RULES: &[&str] = &[ForbiddenFolder::ID, ImgAlt::ID, HeadingOrder::ID];
+
+/// A markdown file, split into its front matter and body.
+struct Source<'a> {
+    path: &'a Path,
+    text: &'a str,
+    front_matter: Option<Block<'a>>,
+    body_offset: usize,
+    source_map: SourceMap,
+}
+
+impl<'a> Source<'a> {
+    fn parse(path: &'a Path, text: &'a str) -> Source<'a> {
+        let (front_matter, body_offset) = frontmatter::split(text);
+        Source {
+            path,
+            text,
+            front_matter,
+            body_offset,
+            source_map: SourceMap::for_text(text),
+        }
+    }
+
+    fn body(&self) -> &'a str {
+        &self.text[self.body_offset..]
+    }
+
+    fn location_of_offset(&self, offset: usize) -> Location {
+        self.source_map.location_of_offset(self.text, offset)
+    }
+}
+
+pub fn check_markdown_file(
+    path: &Path,
+    conf: &ReaderConfig,
+    site: Option<&Site>,
+) -> Vec<CheckError> {
+    let contents = match read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return vec![CheckError::io(path, e)],
+    };
+    let source = Source::parse(path, &contents);
+    let front_matter = source
+        .front_matter
+        .map(|block| FrontMatter::read(path, block, conf.generator));
+
+    let mut errors = check_front_matter(&source, front_matter.as_ref(), conf);
+    // templates render the title as the h1 of the page, so the body starts below it
+    let has_title = match &front_matter {
+        Some(Ok(f)) => f.title.as_deref().is_some_and(|t| !t.trim().is_empty()),
+        _ => false,
+    };
+    errors.extend(check_body(&source, has_title, conf, site));
+    errors
+}
+
+/// The markdown rendered to HTML, along with what was found while rendering it.
+struct Rendered {
+    html: String,
+    /// The offsets in the html and in the body where each top level block starts.
+    blocks: Vec<(usize, usize)>,
+    /// Destinations of links and images, with their offset in the body.
+    links: Vec<(String, usize)>,
+    /// Images without alt text, with their offset in the body.
+    missing_alts: Vec<(String, usize)>,
+}
+
+impl Rendered {
+    fn html_offset_of(&self, body_offset: usize) -> usize {
+        let i = self.blocks.partition_point(|(_, b)| *b <= body_offset);
+        self.blocks.get(i.saturating_sub(1)).map_or(0, |(h, _)| *h)
+    }
+
+    fn body_offset_of(&self, html_offset: usize) -> usize {
+        let i = self.blocks.partition_point(|(h, _)| *h <= html_offset);
+        self.blocks.get(i.saturating_sub(1)).map_or(0, |(_, b)| *b)
+    }
+}
+
+fn render(body: &str) -> Rendered {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut rendered = Rendered {
+        html: String::new(),
+        blocks: vec![],
+        links: vec![],
+        missing_alts: vec![],
+    };
+    // each top level block is rendered on its own, so we know where it ends up in the html
+    let mut block = vec![];
+    let mut block_start = 0;
+    let mut depth = 0;
+    let mut image: Option<(String, usize, String)> = None;
+    for (event, range) in Parser::new_ext(body, options).into_offset_iter() {
+        if depth == 0 {
+            block_start = range.start;
+        }
+        match &event {
+            Event::Start(tag) => {
+                depth += 1;
+                match tag {
+                    Tag::Link(_, dest, _) => rendered.links.push((dest.to_string(), range.start)),
+                    Tag::Image(_, dest, _) => {
+                        rendered.links.push((dest.to_string(), range.start));
+                        image = Some((dest.to_string(), range.start, String::new()));
+                    }
+                    _ => (),
+                }
+            }
+            Event::End(tag) => {
+                depth -= 1;
+                if let Tag::Image(..) = tag {
+                    if let Some((dest, offset, alt)) = image.take() {
+                        if alt.trim().is_empty() {
+                            rendered.missing_alts.push((dest, offset));
+                        }
+                    }
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, alt)) = &mut image {
+                    alt.push_str(text);
+                }
+            }
+            _ => (),
+        }
+        block.push(event);
+
+        if depth == 0 {
+            rendered.blocks.push((rendered.html.len(), block_start));
+            push_html(&mut rendered.html, block.drain(..));
+        }
+    }
+    rendered
+}
+
+/// Runs the html rules on the rendered markdown, and checks the links and images
+/// found while rendering it. Findings point at the start of the block they're in.
+/// Markdown images without alt text are rendered with an empty `alt`, which the html
+/// rule takes for a decorative image, so they're reported from the markdown instead.
+/// Decorative images can still be written as `<img alt="">`.
+fn check_body(
+    source: &Source,
+    has_title: bool,
+    conf: &ReaderConfig,
+    site: Option<&Site>,
+) -> Vec<CheckError> {
+    let rendered = render(source.body());
+    let mut page = Page::parse(source.path, &rendered.html);
+    page.site = site;
+    if has_title {
+        page.outer_heading = Some(1);
+    }
+
+    let rendered_rules = html::rules()
+        .iter()
+        .copied()
+        .filter(|rule| RENDERED_RULES.contains(&rule.id()))
+        .collect::<Vec<_>>();
+    let (mut errors, mut ran) = run_checks(&rendered_rules, &page, conf);
+
+    // findings in the body are first placed in the html, so suppression comments apply to them
+    let in_html = |offset| Some(page.location_of_offset(rendered.html_offset_of(offset)));
+
+    if let Some(severity) = conf.rule_severity(ImgAlt::ID, ImgAlt.default_severity()) {
+        errors.extend(rendered.missing_alts.iter().map(|(dest, offset)| {
+            CheckError::AccessibilityError {
+                path: source.path.display().to_string(),
+                offender: dest.clone(),
+                description: "Image without alt text".to_string(),
+                location: in_html(*offset),
+                rule: ImgAlt::ID,
+                severity,
+            }
+        }));
+    }
+
+    let link_severity = conf.rule_severity(InternalLink::ID, InternalLink.default_severity());
+    if let (Some(site), Some(severity)) = (site, link_severity) {
+        ran.push(InternalLink::ID);
+        for (dest, offset) in &rendered.links {
+            let target = match link_target(site, source.path, &conf.root_dir, dest) {
+                Some(t) => t,
+                None => continue,
+            };
+            // zola sections are folders with an `_index.md`, so a link to the folder is fine
+            let is_folder =
+                target.ends_with("index.html") && target.parent().is_some_and(Path::is_dir);
+            if !target.exists() && !is_folder {
+                errors.push(CheckError::ContentError {
+                    path: source.path.display().to_string(),
+                    offender: dest.clone(),
+                    description: "Link points to a file that doesn't exist".to_string(),
+                    location: in_html(*offset),
+                    rule: InternalLink::ID,
+                    severity,
+                });
+            }
+        }
+    }
+
+    errors.sort_by_key(|e| e.location());
+    apply_suppressions(&page, errors, &ran, conf)
+        .into_iter()
+        .map(|err| {
+            let location = err.location().map(|l| {
+                source.location_of_offset(source.body_offset + rendered.body_offset_of(l.offset))
+            });
+            err.with_location(location)
+        })
+        .collect()
+}
+
+/// The file a link in the markdown points to. Links starting with `@/` point into the
+/// `content` folder, like in zola. Other absolute links point into the built site
+/// rather than the sources, so those can't be checked here.
+fn link_target(site: &Site, path: &Path, root: &Path, dest: &str) -> Option<PathBuf> {
+    if let Some(internal) = dest.strip_prefix("@/") {
+        let content = path
+            .ancestors()
+            .find(|p| p.file_name().is_some_and(|name| name == "content"))
+            .unwrap_or(root);
+        let internal = internal.split(['#', '?']).next().unwrap_or_default();
+        let target = content.join(internal);
+        site.depend(path, &target);
+        return Some(target);
+    }
+    if dest.starts_with('/') {
+        return None;
+    }
+    site.resolve(path, dest).map(|link| link.target)
+}
+
+fn check_front_matter(
+    source: &Source,
+    front_matter: Option<&Result<FrontMatter, String>>,
+    conf: &ReaderConfig,
+) -> Vec<CheckError> {
+    let (block, front_matter) = match (source.front_matter, front_matter) {
+        (Some(b), Some(f)) => (b, f),
+        _ => return vec![],
+    };
+    let enabled = |rule: &'static str, default: Severity| {
+        conf.rule_severity(rule, default)
+            .map(|severity| (rule, severity))
+    };
+    let finding = |(rule, severity), offender: String, description: &str, at: usize| {
+        CheckError::ContentError {
+            path: source.path.display().to_string(),
+            offender,
+            description: description.to_string(),
+            location: Some(source.location_of_offset(at)),
+            rule,
+            severity,
+        }
+    };
+
+    let front_matter = match front_matter {
+        Ok(f) => f,
+        Err(e) => {
+            return enabled(FRONT_MATTER.id, FRONT_MATTER.default_severity)
+                .map(|rule| finding(rule, "".to_string(), e, block.offset))
+                .into_iter()
+                .collect()
+        }
+    };
+
+    let mut errors = vec![];
+    if let Some(rule) = enabled(PageTitle::ID, PageTitle.default_severity()) {
+        if front_matter
+            .title
+            .as_deref()
+            .unwrap_or_default()
+            .trim()
+            .is_empty()
+        {
+            let at = front_matter.key_offset("title");
+            errors.push(finding(rule, "".to_string(), "Page has no title", at));
+        }
+    }
+
+    if let Some(rule) = enabled(DRAFT.id, DRAFT.default_severity) {
+        if front_matter.draft {
+            let offender = front_matter.title.clone().unwrap_or_default();
+            let at = front_matter.key_offset("draft");
+            errors.push(finding(rule, offender, "Page is a draft", at));
+        }
+    }
+
+    if let Some(rule) = enabled(ForbiddenTag::ID, ForbiddenTag.default_severity()) {
+        let forbidden_tags = conf
+            .forbidden_tags
+            .iter()
+            .map(|t| t.to_lowercase())
+            .collect::<HashSet<_>>();
+        for tag in front_matter
+            .tags()
+            .iter()
+            .filter(|t| forbidden_tags.contains(&t.to_lowercase()))
+        {
+            let at = front_matter.value_offset("tags", tag);
+            errors.push(finding(rule, tag.clone(), "Forbidden tag", at));
+        }
+    }
+
+    if let Some(rule) = enabled(ForbiddenDate::ID, ForbiddenDate.default_severity()) {
+        let today = today();
+        if let Some(date) = front_matter
+            .date
+            .filter(|date| conf.date_rules.iter().any(|r| r.matches(*date, today)))
+        {
+            let at = front_matter.key_offset("date");
+            let offender = date.format("%Y-%m-%d").to_string();
+            errors.push(finding(rule, offender, "Forbidden publish date", at));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Generator, RuleLevel};
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    fn check(
+        root: &Path,
+        file: &str,
+        contents: &str,
+        conf: &ReaderConfig,
+    ) -> Vec<(String, String, usize)> {
+        let path = root.join(file);
+        create_dir_all(path.parent().unwrap()).expect("failed to create folder");
+        write(&path, contents).expect("failed to write file");
+        let site = Site::index(root);
+
+        check_markdown_file(&path, conf, Some(&site))
+            .iter()
+            .map(|e| {
+                (
+                    e.rule().to_string(),
+                    e.offender().to_string(),
+                    e.location().map_or(0, |l| l.line),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_discovers_front_matter_problems() {
+        let root = TempDir::new().expect("could not create temp dir");
+        let conf = ReaderConfig {
+            forbidden_tags: vec!["WIP".to_string()],
+            forbidden_dates: vec!["<1950-01-01".to_string()],
+            date_rules: vec!["<1950-01-01".parse().unwrap()],
+            ..ReaderConfig::default()
+        };
+
+        let res = check(
+            root.path(),
+            "content/post.md",
+            r#"+++
+title = "Old news"
+date = 1900-01-01
+draft = true
+
+[taxonomies]
+tags = ["rust", "wip"]
++++
+# Old news
+"#,
+            &conf,
+        );
+
+        assert_eq!(
+            res,
+            vec![
+                ("draft".to_string(), "Old news".to_string(), 4),
+                ("forbidden-tag".to_string(), "wip".to_string(), 7),
+                ("forbidden-date".to_string(), "1900-01-01".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reads_front_matter_of_configured_generator() {
+        let root = TempDir::new().expect("could not create temp dir");
+        let conf = ReaderConfig {
+            forbidden_tags: vec!["wip".to_string()],
+            generator: Generator::Hugo,
+            ..ReaderConfig::default()
+        };
+
+        let res = check(
+            root.path(),
+            "content/post.md",
+            "---\ndraft: false\ntags:\n  - rust\n  - wip\n---\n# Post\n",
+            &conf,
+        );
+
+        assert_eq!(
+            res,
+            vec![
+                ("page-title".to_string(), "".to_string(), 2),
+                ("forbidden-tag".to_string(), "wip".to_string(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_unparsable_front_matter() {
+        let root = TempDir::new().expect("could not create temp dir");
+
+        let res = check(
+            root.path(),
+            "post.md",
+            "+++\ntitle = \n+++\n# Title\n",
+            &ReaderConfig::default(),
+        );
+
+        assert_eq!(res, vec![("front-matter".to_string(), "".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_checks_rendered_markdown() {
+        let root = TempDir::new().expect("could not create temp dir");
+        create_dir_all(root.path().join("content/blog")).expect("failed to create folder");
+        write(root.path().join("content/blog/other.md"), "# Other").expect("failed to write file");
+        write(root.path().join("content/blog/cat.png"), "").expect("failed to write file");
+
+        let res = check(
+            root.path(),
+            "content/blog/post.md",
+            r#"+++
+title = "Post"
++++
+## Intro
+
+#### Skipped a level
+
+![](cat.png) and ![a dog](dog.png)
+
+<img src="cat.png">
+
+See [the other post](@/blog/other.md#intro), [this one](@/blog/missing.md)
+and [the tags](/tags/rust/).
+
+<!-- proof-reader-disable-next-line img-alt -->
+<img src="cat.png">
+"#,
+            &ReaderConfig::default(),
+        );
+
+        // the title is the h1
+        assert_eq!(
+            res,
+            vec![
+                (
+                    "heading-order".to_string(),
+                    "<h4> Skipped a level".to_string(),
+                    6
+                ),
+                ("img-alt".to_string(), "cat.png".to_string(), 8),
+                ("internal-link".to_string(), "dog.png".to_string(), 8),
+                (
+                    "img-alt".to_string(),
+                    "<img src=\"cat.png\">".to_string(),
+                    10
+                ),
+                (
+                    "internal-link".to_string(),
+                    "@/blog/missing.md".to_string(),
+                    12
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_body_starts_with_h1_without_title() {
+        let root = TempDir::new().expect("could not create temp dir");
+        let mut conf = ReaderConfig::default();
+        conf.rules.insert("page-title".to_string(), RuleLevel::Off);
+
+        let res = check(
+            root.path(),
+            "post.md",
+            "+++\ndraft = false\n+++\n## Intro\n",
+            &conf,
+        );
+
+        assert_eq!(
+            res,
+            vec![("heading-order".to_string(), "<h2> Intro".to_string(), 4)]
+        );
+    }
+
+    #[test]
+    fn test_reports_images_without_alt_text() {
+        let root = TempDir::new().expect("could not create temp dir");
+        write(root.path().join("a.png"), "").expect("failed to write file");
+        write(root.path().join("b.png"), "").expect("failed to write file");
+        write(root.path().join("c.png"), "").expect("failed to write file");
+
+        let res = check(
+            root.path(),
+            "post.md",
+            "+++\ntitle = \"Post\"\n+++\n![](a.png)\n\n![  ](b.png)\n\n![`code`](c.png)\n",
+            &ReaderConfig::default(),
+        );
+
+        assert_eq!(
+            res,
+            vec![
+                ("img-alt".to_string(), "a.png".to_string(), 4),
+                ("img-alt".to_string(), "b.png".to_string(), 6),
+            ]
+        );
+    }
+}
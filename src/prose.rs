@@ -0,0 +1,158 @@
+use lazy_static::*;
+use regex::Regex;
+use scraper::{ElementRef, Node, Selector};
+
+use crate::html::Page;
+
+/// Elements whose text is shown as is rather than read, so it's kept in
+/// the sentences around it but never checked.
+const NOT_PROSE: &[&str] = &["code", "pre", "kbd", "samp", "var"];
+
+/// Elements whose text isn't shown at all.
+const HIDDEN: &[&str] = &["script", "style", "template", "noscript", "svg"];
+
+/// Elements that can't be part of a sentence that started before them.
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "caption",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+lazy_static! {
+    static ref RE_SENTENCE_END: Regex = Regex::new(r"[.!?]+(\s|$)|\n\s*\n").unwrap();
+}
+
+/// The text of a page as it's read, made of pieces of text that each know
+/// which element they're in.
+#[derive(Default)]
+pub struct Prose<'a> {
+    pub text: String,
+    /// Where each piece starts in `text`, its element and whether it's checked.
+    pieces: Vec<(usize, ElementRef<'a>, bool)>,
+}
+
+impl<'a> Prose<'a> {
+    /// The prose in the `main` and `article` elements of the page.
+    pub fn of_page(page: &'a Page) -> Prose<'a> {
+        Prose::of_selected(page, &Selector::parse("main, article").unwrap())
+    }
+
+    /// The text of every element matching `selector`, each one apart from the others.
+    pub fn of_selected(page: &'a Page, selector: &Selector) -> Prose<'a> {
+        let mut prose = Prose::default();
+        for elem in page.document.select(selector).filter(|elem| {
+            // nested ones are already part of the outer one
+            !elem
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|a| selector.matches(&a))
+        }) {
+            prose.collect(elem, true);
+            prose.text.push_str("\n\n");
+        }
+        prose
+    }
+
+    fn collect(&mut self, elem: ElementRef<'a>, checked: bool) {
+        for child in elem.children() {
+            match child.value() {
+                Node::Text(text) => {
+                    self.pieces.push((self.text.len(), elem, checked));
+                    self.text.push_str(text);
+                }
+                Node::Element(e) => {
+                    let name = e.name();
+                    if HIDDEN.contains(&name) || e.attr("hidden").is_some() {
+                        continue;
+                    }
+                    let block = BLOCKS.contains(&name);
+                    if block {
+                        self.text.push_str("\n\n");
+                    }
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.collect(child, checked && !NOT_PROSE.contains(&name));
+                    }
+                    if block {
+                        self.text.push_str("\n\n");
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// The element the text at `offset` is in, unless it's not checked like code.
+    pub fn checked_element_at(&self, offset: usize) -> Option<ElementRef<'a>> {
+        let i = self
+            .pieces
+            .partition_point(|(start, _, _)| *start <= offset);
+        match self.pieces.get(i.checked_sub(1)?) {
+            Some((_, element, true)) => Some(*element),
+            _ => None,
+        }
+    }
+
+    /// Every sentence along with its offset, blank ones left out.
+    pub fn sentences(&self) -> impl Iterator<Item = (usize, &str)> + '_ {
+        let mut start = 0;
+        RE_SENTENCE_END
+            .find_iter(&self.text)
+            .map(|m| (m.start(), m.end()))
+            .chain(std::iter::once((self.text.len(), self.text.len())))
+            .filter_map(move |(end, next)| {
+                let sentence = (start, &self.text[start..end]);
+                start = next;
+                Some(sentence).filter(|(_, s)| !s.trim().is_empty())
+            })
+    }
+
+    /// The sentence around `offset`, on a single line.
+    pub fn sentence_around(&self, offset: usize) -> String {
+        let start = RE_SENTENCE_END
+            .find_iter(&self.text[..offset])
+            .last()
+            .map_or(0, |m| m.end());
+        let end = RE_SENTENCE_END
+            .find(&self.text[offset..])
+            .map_or(self.text.len(), |m| {
+                offset + m.start() + m.as_str().trim_end().len()
+            });
+        self.text[start..end]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
@@ -0,0 +1,264 @@
+use lazy_static::*;
+use regex::Regex;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::html::Page;
+use crate::prose::Prose;
+
+/// Forms of "to be" that start a passive construction.
+const TO_BE: &[&str] = &["am", "is", "are", "was", "were", "be", "been", "being"];
+
+/// Adverbs that can come between "to be" and the participle, besides ones ending in -ly.
+const ADVERBS: &[&str] = &[
+    "also", "already", "always", "never", "not", "often", "seldom", "still", "then",
+];
+
+/// Past participles that don't end in -ed.
+const IRREGULAR_PARTICIPLES: &[&str] = &[
+    "born",
+    "bought",
+    "broken",
+    "brought",
+    "built",
+    "caught",
+    "chosen",
+    "done",
+    "drawn",
+    "driven",
+    "eaten",
+    "fed",
+    "felt",
+    "forgotten",
+    "found",
+    "given",
+    "grown",
+    "heard",
+    "held",
+    "hidden",
+    "kept",
+    "known",
+    "laid",
+    "led",
+    "left",
+    "lost",
+    "made",
+    "meant",
+    "met",
+    "paid",
+    "put",
+    "read",
+    "said",
+    "seen",
+    "sent",
+    "set",
+    "shown",
+    "sold",
+    "spent",
+    "spoken",
+    "stolen",
+    "taken",
+    "taught",
+    "thought",
+    "thrown",
+    "told",
+    "torn",
+    "understood",
+    "won",
+    "worn",
+    "written",
+];
+
+/// How readable the prose of a page is.
+#[derive(Debug, PartialEq)]
+pub struct Readability {
+    pub lang: Option<String>,
+    pub words: usize,
+    pub sentences: usize,
+    /// Flesch reading ease, adapted to the language of the page,
+    /// `None` for languages there's no formula for.
+    pub reading_ease: Option<f64>,
+    /// Average number of words per sentence.
+    pub sentence_length: f64,
+    /// Share of sentences in the passive voice, only known for English.
+    pub passive_ratio: Option<f64>,
+}
+
+/// Flesch reading ease from the average sentence length and syllables per word,
+/// using the variant for the language where there is one.
+fn reading_ease(lang: &str, sentence_length: f64, syllables: f64) -> Option<f64> {
+    let ease = match lang {
+        "en" => 206.835 - 1.015 * sentence_length - 84.6 * syllables,
+        // Amstad
+        "de" => 180.0 - sentence_length - 58.5 * syllables,
+        // Kandel and Moles
+        "fr" => 207.0 - 1.015 * sentence_length - 73.6 * syllables,
+        // Douma
+        "nl" => 206.835 - 0.93 * sentence_length - 77.0 * syllables,
+        // Szigriszt-Pazos
+        "es" => 206.835 - sentence_length - 62.3 * syllables,
+        _ => return None,
+    };
+    Some(ease)
+}
+
+/// Estimates syllables by counting groups of vowels, which is close enough on
+/// average even though it's wrong for plenty of words.
+fn syllables(word: &str, lang: &str) -> usize {
+    lazy_static! {
+        static ref RE_VOWELS: Regex = Regex::new(r"[aeiouyàáâäæèéêëìíîïòóôöøœùúûüÿ]+").unwrap();
+    }
+    let word = word.to_lowercase();
+    let mut count = RE_VOWELS.find_iter(&word).count();
+    // a silent e, like in "make", but not in "table"
+    if lang == "en" && count > 1 && word.ends_with('e') && !word.ends_with("le") {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+fn is_passive(words: &[String]) -> bool {
+    let participle = |w: &String| {
+        (w.len() > 3 && w.ends_with("ed")) || IRREGULAR_PARTICIPLES.contains(&w.as_str())
+    };
+    words.windows(2).enumerate().any(|(i, pair)| {
+        if !TO_BE.contains(&pair[0].as_str()) {
+            return false;
+        }
+        // allow an adverb in between, like "is often used"
+        participle(&pair[1])
+            || ((pair[1].ends_with("ly") || ADVERBS.contains(&pair[1].as_str()))
+                && words.get(i + 2).is_some_and(participle))
+    })
+}
+
+/// Measures the prose in the `main` and `article` elements of the page, code left out.
+/// Returns `None` if there's no prose at all.
+pub fn measure(page: &Page) -> Option<Readability> {
+    lazy_static! {
+        static ref RE_WORD: Regex = Regex::new(r"\p{L}+(?:['’]\p{L}+)*").unwrap();
+    }
+    let lang = page
+        .document
+        .root_element()
+        .value()
+        .attr("lang")
+        .map(|l| l.to_string());
+    let base_lang = lang
+        .as_deref()
+        .and_then(|l| l.split(['-', '_']).next())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let prose = Prose::of_page(page);
+    let (mut words, mut sentences, mut syllable_count, mut passive) = (0, 0, 0, 0);
+    for (offset, sentence) in prose.sentences() {
+        let sentence_words = RE_WORD
+            .find_iter(sentence)
+            .filter(|w| prose.checked_element_at(offset + w.start()).is_some())
+            .map(|w| w.as_str().to_lowercase())
+            .collect::<Vec<_>>();
+        if sentence_words.is_empty() {
+            continue;
+        }
+        words += sentence_words.len();
+        sentences += 1;
+        syllable_count += sentence_words
+            .iter()
+            .map(|w| syllables(w, &base_lang))
+            .sum::<usize>();
+        if is_passive(&sentence_words) {
+            passive += 1;
+        }
+    }
+    if words == 0 {
+        return None;
+    }
+
+    let sentence_length = words as f64 / sentences as f64;
+    Some(Readability {
+        words,
+        sentences,
+        reading_ease: reading_ease(
+            &base_lang,
+            sentence_length,
+            syllable_count as f64 / words as f64,
+        ),
+        sentence_length,
+        passive_ratio: Some(passive as f64 / sentences as f64).filter(|_| base_lang == "en"),
+        lang,
+    })
+}
+
+/// Measures the HTML file at `path`, `None` if it can't be read or has no prose.
+pub fn measure_file(path: &Path) -> Option<Readability> {
+    let contents = read_to_string(path).ok()?;
+    measure(&Page::parse(path, &contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure_html(html: &str) -> Readability {
+        let page = Page::parse(Path::new("page.html"), html);
+        measure(&page).expect("page should have prose")
+    }
+
+    #[test]
+    fn test_counts_syllables() {
+        assert_eq!(syllables("cat", "en"), 1);
+        assert_eq!(syllables("make", "en"), 1);
+        assert_eq!(syllables("table", "en"), 2);
+        assert_eq!(syllables("readability", "en"), 5);
+        assert_eq!(syllables("the", "en"), 1);
+        assert_eq!(syllables("Lesbarkeit", "de"), 3);
+    }
+
+    #[test]
+    fn test_measures_english_prose() {
+        let readability = measure_html(
+            r#"<html lang="en-GB"><body><nav>Skip this menu</nav><main>
+            <p>The cat sat on the mat. The mat was made by hand.</p>
+            <p>It is red. <code>let x = a.b(c);</code></p>
+            </main></body></html>"#,
+        );
+
+        assert_eq!(readability.lang.as_deref(), Some("en-GB"));
+        assert_eq!(readability.words, 15);
+        assert_eq!(readability.sentences, 3);
+        assert_eq!(readability.passive_ratio, Some(1.0 / 3.0));
+        // one syllable per word, five words a sentence
+        let ease = readability.reading_ease.expect("english has a formula");
+        assert!((ease - 117.16).abs() < 0.01, "got {}", ease);
+    }
+
+    #[test]
+    fn test_detects_passive_voice() {
+        let words = |s: &str| s.split(' ').map(str::to_string).collect::<Vec<_>>();
+        assert!(is_passive(&words("the page was written by hand")));
+        assert!(is_passive(&words("it is often used")));
+        assert!(!is_passive(&words("we wrote the page")));
+        assert!(!is_passive(&words("she is red")));
+    }
+
+    #[test]
+    fn test_other_languages() {
+        let readability = measure_html(
+            r#"<html lang="de"><body><article><p>Das ist ein Satz.</p></article></body></html>"#,
+        );
+        assert!(readability.reading_ease.is_some());
+        assert_eq!(readability.passive_ratio, None);
+
+        let readability = measure_html(
+            r#"<html lang="fi"><body><article><p>Tämä on lause.</p></article></body></html>"#,
+        );
+        assert_eq!(readability.reading_ease, None);
+
+        let page = Page::parse(
+            Path::new("page.html"),
+            "<html><body><main></main></body></html>",
+        );
+        assert_eq!(measure(&page), None);
+    }
+}
@@ -0,0 +1,411 @@
+use clap::crate_version;
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::dispatch::rules;
+use crate::error::{CheckError, Severity};
+use crate::readability::Readability;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    /// Colored, one line per finding, meant to be read in a terminal.
+    Human,
+    /// One JSON object per line per finding, optionally followed by
+    /// one `{"readability": ...}` object per page.
+    Json,
+    /// A SARIF 2.1.0 log, for code scanning UIs.
+    Sarif,
+    /// A JUnit XML report, for CI test reporters.
+    Junit,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            "sarif" => Ok(Format::Sarif),
+            "junit" => Ok(Format::Junit),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+pub fn write_findings(
+    format: Format,
+    findings: &[CheckError],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    match format {
+        Format::Human => write_human(findings, out),
+        Format::Json => write_json_lines(findings, out),
+        Format::Sarif => write_sarif(findings, out),
+        Format::Junit => write_junit(findings, out),
+    }
+}
+
+fn write_human(findings: &[CheckError], out: &mut dyn Write) -> io::Result<()> {
+    for finding in findings {
+        writeln!(out, "{}", finding)?;
+    }
+    Ok(())
+}
+
+fn finding_to_json(finding: &CheckError) -> Value {
+    let location = finding.location();
+    json!({
+        "rule": finding.rule(),
+        "path": finding.path(),
+        "line": location.map(|l| l.line),
+        "column": location.map(|l| l.column),
+        "offset": location.map(|l| l.offset),
+        "severity": finding.severity().to_string(),
+        "category": finding.category().to_string(),
+        "description": finding.description(),
+        "offender": finding.offender(),
+    })
+}
+
+fn write_json_lines(findings: &[CheckError], out: &mut dyn Write) -> io::Result<()> {
+    for finding in findings {
+        writeln!(out, "{}", finding_to_json(finding))?;
+    }
+    Ok(())
+}
+
+fn readability_to_json(path: &str, readability: &Readability) -> Value {
+    let round = |n: f64| (n * 100.0).round() / 100.0;
+    json!({
+        "readability": {
+            "path": path,
+            "lang": readability.lang,
+            "words": readability.words,
+            "sentences": readability.sentences,
+            "reading_ease": readability.reading_ease.map(round),
+            "sentence_length": round(readability.sentence_length),
+            "passive_ratio": readability.passive_ratio.map(round),
+        }
+    })
+}
+
+/// Writes the readability of every page, one JSON object per line, so that
+/// editors can keep track of it between runs.
+pub fn write_readability_table(
+    table: &[(String, Readability)],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    for (path, readability) in table {
+        writeln!(out, "{}", readability_to_json(path, readability))?;
+    }
+    Ok(())
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+fn sarif_log(findings: &[CheckError]) -> Value {
+    let rules = rules();
+    let results = findings
+        .iter()
+        .map(|finding| {
+            let mut result = json!({
+                "ruleId": finding.rule(),
+                "level": sarif_level(finding.severity()),
+                "message": { "text": finding.message() },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.path().replace('\\', "/") }
+                    }
+                }],
+                "properties": {
+                    "category": finding.category().to_string(),
+                    "offender": finding.offender(),
+                },
+            });
+            if let Some(location) = finding.location() {
+                let mut region = json!({
+                    "startLine": location.line,
+                    "startColumn": location.column,
+                    "byteOffset": location.offset,
+                });
+                if !finding.offender().is_empty() {
+                    region["snippet"] = json!({ "text": finding.offender() });
+                }
+                result["locations"][0]["physicalLocation"]["region"] = region;
+            }
+            if let Some(index) = rules.iter().position(|r| r.id == finding.rule()) {
+                result["ruleIndex"] = json!(index);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+
+    let sarif_rules = rules
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id,
+                "name": rule.id,
+                "shortDescription": { "text": rule.name },
+                "defaultConfiguration": { "level": sarif_level(rule.default_severity) },
+                "properties": { "category": rule.category.to_string() },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "proof-reader",
+                    "version": crate_version!(),
+                    "rules": sarif_rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn write_sarif(findings: &[CheckError], out: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &sarif_log(findings))?;
+    writeln!(out)
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Every finding becomes a test case; errors fail it, warnings only leave a note in its output.
+fn write_junit(findings: &[CheckError], out: &mut dyn Write) -> io::Result<()> {
+    let failures = findings
+        .iter()
+        .filter(|f| f.severity() == Severity::Error)
+        .count();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="proof-reader" tests="{}" failures="{}">"#,
+        findings.len(),
+        failures
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="proof-reader" tests="{}" failures="{}" errors="0">"#,
+        findings.len(),
+        failures
+    )?;
+    for finding in findings {
+        writeln!(
+            out,
+            r#"    <testcase classname="{}" name="{}">"#,
+            xml_escape(finding.path()),
+            xml_escape(finding.rule())
+        )?;
+        match finding.severity() {
+            Severity::Error => writeln!(
+                out,
+                r#"      <failure message="{}" type="{}">{}: {}</failure>"#,
+                xml_escape(&finding.description()),
+                xml_escape(finding.rule()),
+                xml_escape(&finding.position()),
+                xml_escape(finding.offender())
+            )?,
+            Severity::Warning => writeln!(
+                out,
+                "      <system-out>warning: {}: {} {}</system-out>",
+                xml_escape(&finding.position()),
+                xml_escape(&finding.description()),
+                xml_escape(finding.offender())
+            )?,
+        }
+        writeln!(out, "    </testcase>")?;
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+
+    fn setup_test_findings() -> Vec<CheckError> {
+        vec![
+            CheckError::AccessibilityError {
+                path: "public/index.html".to_string(),
+                offender: "<img src=\"a&b.jpg\">".to_string(),
+                description: "Image tag without alt".to_string(),
+                location: Some(Location {
+                    offset: 120,
+                    line: 7,
+                    column: 5,
+                }),
+                rule: "img-alt",
+                severity: Severity::Error,
+            },
+            CheckError::ContentError {
+                path: "public/wip.html".to_string(),
+                offender: "wip".to_string(),
+                description: "Forbidden tag".to_string(),
+                location: None,
+                rule: "forbidden-tag",
+                severity: Severity::Warning,
+            },
+        ]
+    }
+
+    fn render(format: Format) -> String {
+        let mut out = vec![];
+        write_findings(format, &setup_test_findings(), &mut out).expect("failed to write");
+        String::from_utf8(out).expect("output is not utf-8")
+    }
+
+    #[test]
+    fn test_json_lines_has_one_object_per_finding() {
+        let output = render(Format::Json);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        let first: Value = serde_json::from_str(lines[0]).expect("invalid json");
+        assert_eq!(first["rule"], "img-alt");
+        assert_eq!(first["path"], "public/index.html");
+        assert_eq!(first["severity"], "error");
+        assert_eq!(first["category"], "accessibility");
+        assert_eq!(first["description"], "Image tag without alt");
+        assert_eq!(first["offender"], "<img src=\"a&b.jpg\">");
+        assert_eq!(first["line"], 7);
+        assert_eq!(first["column"], 5);
+        assert_eq!(first["offset"], 120);
+
+        let second: Value = serde_json::from_str(lines[1]).expect("invalid json");
+        assert_eq!(second["severity"], "warning");
+        assert_eq!(second["line"], Value::Null);
+    }
+
+    #[test]
+    fn test_sarif_log_structure() {
+        let log: Value = serde_json::from_str(&render(Format::Sarif)).expect("invalid json");
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "proof-reader");
+
+        let results = run["results"]
+            .as_array()
+            .expect("results should be an array");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], "img-alt");
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[1]["level"], "warning");
+        assert_eq!(
+            results[0]["message"]["text"],
+            "Image tag without alt: <img src=\"a&b.jpg\">"
+        );
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "public/index.html");
+        assert_eq!(location["region"]["startLine"], 7);
+        assert_eq!(location["region"]["startColumn"], 5);
+        assert_eq!(
+            location["region"]["snippet"]["text"],
+            "<img src=\"a&b.jpg\">"
+        );
+        assert!(results[1]["locations"][0]["physicalLocation"]
+            .get("region")
+            .is_none());
+
+        // ruleIndex has to point at the matching rule in the driver
+        let index = results[0]["ruleIndex"].as_u64().expect("missing ruleIndex") as usize;
+        assert_eq!(run["tool"]["driver"]["rules"][index]["id"], "img-alt");
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let output = render(Format::Junit);
+
+        assert!(output.starts_with("<?xml"));
+        assert!(output.contains(r#"<testsuites name="proof-reader" tests="2" failures="1">"#));
+        assert!(output.contains(r#"<testcase classname="public/index.html" name="img-alt">"#));
+        assert!(output
+            .contains("public/index.html:7:5: &lt;img src=&quot;a&amp;b.jpg&quot;&gt;</failure>"));
+        assert!(
+            output.contains("<system-out>warning: public/wip.html: Forbidden tag wip</system-out>")
+        );
+    }
+
+    #[test]
+    fn test_io_errors_name_the_file() {
+        let findings = vec![CheckError::io(
+            std::path::Path::new("public/broken.html"),
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            ),
+        )];
+        let mut out = vec![];
+        write_findings(Format::Sarif, &findings, &mut out).expect("failed to write");
+        let log: Value = serde_json::from_slice(&out).expect("invalid json");
+
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "io-error");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "public/broken.html"
+        );
+        assert_eq!(finding_to_json(&findings[0])["path"], "public/broken.html");
+    }
+
+    #[test]
+    fn test_readability_table() {
+        let table = vec![(
+            "public/index.html".to_string(),
+            Readability {
+                lang: Some("en".to_string()),
+                words: 120,
+                sentences: 9,
+                reading_ease: Some(61.2345),
+                sentence_length: 13.3333,
+                passive_ratio: None,
+            },
+        )];
+        let mut out = vec![];
+        write_readability_table(&table, &mut out).expect("failed to write");
+
+        let row: Value = serde_json::from_slice(&out).expect("invalid json");
+        let row = &row["readability"];
+        assert_eq!(row["path"], "public/index.html");
+        assert_eq!(row["words"], 120);
+        assert_eq!(row["reading_ease"], 61.23);
+        assert_eq!(row["sentence_length"], 13.33);
+        assert_eq!(row["passive_ratio"], Value::Null);
+    }
+
+    #[test]
+    fn test_parses_formats() {
+        assert_eq!("sarif".parse::<Format>(), Ok(Format::Sarif));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,342 @@
+use lazy_static::*;
+use rayon::prelude::*;
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{read, read_to_string};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::WalkDir;
+
+use crate::cache::hash;
+
+/// The built site being checked, used to resolve links between its files.
+#[derive(Debug)]
+pub struct Site {
+    root: PathBuf,
+    /// The ids and `a[name]`s of every HTML file in the site.
+    anchors: HashMap<PathBuf, HashSet<String>>,
+    /// Every file a link was resolved to, keyed by the file the link is in,
+    /// so the cache knows which files the findings of a file depend on.
+    dependencies: Mutex<HashMap<PathBuf, BTreeSet<PathBuf>>>,
+}
+
+/// A link that points into the site, split into the file it resolves to and its fragment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InternalLink {
+    pub target: PathBuf,
+    pub fragment: Option<String>,
+}
+
+impl Site {
+    /// Reads every HTML file under `root`, excluded or not, since links to
+    /// excluded pages still have to land on something that exists.
+    pub fn index(root: &Path) -> Site {
+        Site::index_with(root, |_, _| None)
+    }
+
+    /// Like `index`, but asks `known` for the anchors of a file before parsing it.
+    pub fn index_with<F>(root: &Path, known: F) -> Site
+    where
+        F: Fn(&Path, &str) -> Option<HashSet<String>> + Sync,
+    {
+        let anchors = WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "html"))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .filter_map(|e| {
+                // unreadable files are reported when they are checked themselves
+                let contents = read_to_string(e.path()).ok()?;
+                let anchors = known(e.path(), &contents)
+                    .unwrap_or_else(|| anchors(&Html::parse_document(&contents)));
+                Some((e.into_path(), anchors))
+            })
+            .collect();
+
+        Site {
+            root: root.to_path_buf(),
+            anchors,
+            dependencies: Mutex::default(),
+        }
+    }
+
+    /// Every HTML file in the site along with its anchors.
+    pub fn pages(&self) -> impl Iterator<Item = (&PathBuf, &HashSet<String>)> {
+        self.anchors.iter()
+    }
+
+    /// Reads the anchors of the HTML file at `path` again, after it changed or was removed.
+    pub fn reindex(&mut self, path: &Path) {
+        let is_html = path.extension().is_some_and(|ext| ext == "html");
+        match read_to_string(path) {
+            Ok(contents) if is_html => {
+                let anchors = anchors(&Html::parse_document(&contents));
+                self.anchors.insert(path.to_path_buf(), anchors);
+            }
+            // gone, and with it every page inside when it was a folder
+            _ => self.anchors.retain(|page, _| !page.starts_with(path)),
+        }
+    }
+
+    /// Every file that depended on any of `paths` when it was last checked.
+    pub fn dependents(&self, paths: &HashSet<PathBuf>) -> HashSet<PathBuf> {
+        let dependencies = self.dependencies.lock().unwrap();
+        dependencies
+            .iter()
+            .filter(|(_, targets)| targets.iter().any(|t| paths.contains(t)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Records that what's reported for the file at `path` depends on `target`.
+    pub fn depend(&self, path: &Path, target: &Path) {
+        let mut dependencies = self.dependencies.lock().unwrap();
+        dependencies
+            .entry(path.to_path_buf())
+            .or_default()
+            .insert(target.to_path_buf());
+    }
+
+    /// Takes the files the file at `path` has depended on so far.
+    pub fn take_dependencies(&self, path: &Path) -> BTreeSet<PathBuf> {
+        let mut dependencies = self.dependencies.lock().unwrap();
+        dependencies.remove(path).unwrap_or_default()
+    }
+
+    /// Sums up what the checks can see of the file at `path`: the anchors of pages,
+    /// the contents of stylesheets, and only whether anything else exists.
+    pub fn digest(&self, path: &Path) -> String {
+        if let Some(anchors) = self.anchors(path) {
+            let anchors = anchors.iter().collect::<BTreeSet<_>>();
+            let joined = anchors.into_iter().cloned().collect::<Vec<_>>().join("\n");
+            return format!("page {}", hash(joined.as_bytes()));
+        }
+        if path.is_dir() {
+            return "folder".to_string();
+        }
+        match read(path) {
+            // contrast reads linked stylesheets
+            Ok(contents) if path.extension().is_some_and(|e| e == "css") => {
+                format!("stylesheet {}", hash(&contents))
+            }
+            Ok(_) => "file".to_string(),
+            Err(_) => "missing".to_string(),
+        }
+    }
+
+    /// The anchors in the HTML file at `path`, or `None` if it wasn't indexed.
+    pub fn anchors(&self, path: &Path) -> Option<&HashSet<String>> {
+        self.anchors.get(path)
+    }
+
+    /// Resolves `url`, as found on the page at `page_path`, to a file in the site.
+    /// Returns `None` for links that leave the site, like `https://` or `mailto:`.
+    pub fn resolve(&self, page_path: &Path, url: &str) -> Option<InternalLink> {
+        lazy_static! {
+            static ref RE_SCHEME: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap();
+        }
+
+        let url = url.trim();
+        if url.is_empty() || url.starts_with("//") || RE_SCHEME.is_match(url) {
+            return None;
+        }
+
+        let (url, fragment) = match url.split_once('#') {
+            Some((url, fragment)) => (url, Some(percent_decode(fragment))),
+            None => (url, None),
+        };
+        let path = url.split('?').next().unwrap_or_default();
+        if path.is_empty() {
+            // only a fragment or query string, so it's the page itself
+            self.depend(page_path, page_path);
+            return Some(InternalLink {
+                target: page_path.to_path_buf(),
+                fragment,
+            });
+        }
+
+        // URLs are resolved segment by segment, the way a browser would,
+        // so `..` never climbs out of the site
+        let mut segments = if path.starts_with('/') {
+            vec![]
+        } else {
+            self.segments_of(page_path.parent().unwrap_or_else(|| Path::new("")))
+        };
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => (),
+                ".." => {
+                    segments.pop();
+                }
+                s => segments.push(percent_decode(s)),
+            }
+        }
+
+        let mut target = self.root.clone();
+        target.extend(&segments);
+        // depends on the folder too, since the link changes meaning if it stops being one
+        self.depend(page_path, &target);
+        if path.ends_with('/') || target.is_dir() {
+            target.push("index.html");
+        }
+        self.depend(page_path, &target);
+        Some(InternalLink { target, fragment })
+    }
+
+    fn segments_of(&self, dir: &Path) -> Vec<String> {
+        dir.strip_prefix(&self.root)
+            .unwrap_or(dir)
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Every fragment a link into `document` can point to.
+pub fn anchors(document: &Html) -> HashSet<String> {
+    let anchor_selector = Selector::parse("[id], a[name]").unwrap();
+
+    document
+        .select(&anchor_selector)
+        .flat_map(|e| {
+            let elem = e.value();
+            let name = if elem.name() == "a" {
+                elem.attr("name")
+            } else {
+                None
+            };
+            elem.attr("id").into_iter().chain(name)
+        })
+        .map(|a| a.to_string())
+        .collect()
+}
+
+/// Decodes `%XX` escapes, leaving anything that isn't a valid escape as is.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    fn resolve(site: &Site, page: &str, url: &str) -> Option<(PathBuf, Option<String>)> {
+        site.resolve(&site.root.join(page), url).map(|l| {
+            (
+                l.target.strip_prefix(&site.root).unwrap().to_path_buf(),
+                l.fragment,
+            )
+        })
+    }
+
+    #[test]
+    fn test_resolves_relative_and_absolute_links() {
+        let site = Site::index(Path::new("public"));
+
+        assert_eq!(
+            resolve(&site, "posts/a/index.html", "../b/img.png"),
+            Some((PathBuf::from("posts/b/img.png"), None))
+        );
+        assert_eq!(
+            resolve(&site, "posts/a/index.html", "/style.css?v=2"),
+            Some((PathBuf::from("style.css"), None))
+        );
+        assert_eq!(
+            resolve(&site, "index.html", "../../../etc/passwd"),
+            Some((PathBuf::from("etc/passwd"), None))
+        );
+        assert_eq!(
+            resolve(&site, "index.html", "/posts/my%20post/#intro"),
+            Some((
+                PathBuf::from("posts/my post/index.html"),
+                Some("intro".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_fragment_only_links_point_at_the_page() {
+        let site = Site::index(Path::new("public"));
+
+        assert_eq!(
+            resolve(&site, "posts/a.html", "#top"),
+            Some((PathBuf::from("posts/a.html"), Some("top".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_directories_resolve_to_index() {
+        let root = TempDir::new().expect("could not create temp dir");
+        create_dir_all(root.path().join("about")).expect("failed to create folder");
+        let site = Site::index(root.path());
+
+        assert_eq!(
+            resolve(&site, "index.html", "/about"),
+            Some((PathBuf::from("about/index.html"), None))
+        );
+    }
+
+    #[test]
+    fn test_indexes_anchors() {
+        let root = TempDir::new().expect("could not create temp dir");
+        create_dir_all(root.path().join("posts")).expect("failed to create folder");
+        write(
+            root.path().join("posts/a.html"),
+            r#"<h2 id="install">Install</h2><a name="legacy"></a><p name="not-an-anchor"></p>"#,
+        )
+        .expect("failed to write file");
+        write(root.path().join("notes.txt"), r#"<p id="nope"></p>"#).expect("failed to write file");
+        let site = Site::index(root.path());
+
+        let mut found = site
+            .anchors(&root.path().join("posts/a.html"))
+            .expect("page should be indexed")
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, vec!["install", "legacy"]);
+        assert_eq!(site.anchors(&root.path().join("notes.txt")), None);
+    }
+
+    #[test]
+    fn test_ignores_external_links() {
+        let site = Site::index(Path::new("public"));
+
+        for url in &[
+            "https://example.com",
+            "mailto:me@example.com",
+            "//cdn.example.com/lib.js",
+            "",
+        ] {
+            assert_eq!(resolve(&site, "index.html", url), None, "{}", url);
+        }
+    }
+}
@@ -0,0 +1,488 @@
+use lazy_static::*;
+use regex::Regex;
+use scraper::ElementRef;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config::SpellingConfig;
+use crate::html::Page;
+use crate::prose::Prose;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlagType {
+    /// Every character is a flag, the default.
+    Char,
+    /// Every two characters are a flag.
+    Long,
+    /// Flags are numbers separated by commas.
+    Num,
+}
+
+/// A single prefix or suffix rule of an affix file.
+#[derive(Debug)]
+struct Affix {
+    flag: String,
+    is_suffix: bool,
+    /// Whether it can be combined with an affix of the other kind.
+    cross_product: bool,
+    /// Removed from the word before `add` is added.
+    strip: String,
+    add: String,
+    /// What the word has to start or end with for the rule to apply.
+    condition: Regex,
+}
+
+impl Affix {
+    /// The word `word` was made from with this rule, if it could have been.
+    fn remove(&self, word: &str) -> Option<String> {
+        let base = if self.is_suffix {
+            format!("{}{}", word.strip_suffix(self.add.as_str())?, self.strip)
+        } else {
+            format!("{}{}", self.strip, word.strip_prefix(self.add.as_str())?)
+        };
+        Some(base).filter(|b| !b.is_empty() && self.condition.is_match(b))
+    }
+}
+
+/// A word list with affix rules in Hunspell format. Only the parts needed to
+/// check words are read, compounding and suggestions aren't supported.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    /// Every word with the flags of the affixes it takes.
+    words: HashMap<String, HashSet<String>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+}
+
+impl Dictionary {
+    /// Reads a dictionary from the contents of its `.aff` and `.dic` files.
+    pub fn parse(aff: &str, dic: &str) -> Dictionary {
+        let mut dictionary = Dictionary::default();
+        let mut flag_type = FlagType::Char;
+        let mut cross_products = HashMap::new();
+
+        for line in aff.lines().filter(|l| !l.starts_with('#')) {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => flag_type = FlagType::Num,
+                [kind @ ("PFX" | "SFX"), flag, cross, count]
+                    if (*cross == "Y" || *cross == "N") && count.parse::<usize>().is_ok() =>
+                {
+                    cross_products.insert((*kind, *flag), *cross == "Y");
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let is_suffix = *kind == "SFX";
+                    // continuation flags after the affix aren't supported
+                    let add = add.split('/').next().unwrap_or_default();
+                    let affix = Affix {
+                        flag: flag.to_string(),
+                        is_suffix,
+                        cross_product: cross_products.get(&(*kind, *flag)) == Some(&true),
+                        strip: if *strip == "0" { "" } else { strip }.to_string(),
+                        add: if add == "0" { "" } else { add }.to_string(),
+                        condition: condition(rest.first().unwrap_or(&"."), is_suffix),
+                    };
+                    if is_suffix {
+                        dictionary.suffixes.push(affix);
+                    } else {
+                        dictionary.prefixes.push(affix);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut lines = dic.lines().peekable();
+        // the first line is the number of words, which is only a hint
+        if lines
+            .peek()
+            .is_some_and(|l| l.trim().parse::<usize>().is_ok())
+        {
+            lines.next();
+        }
+        for entry in lines.filter_map(|l| l.split_whitespace().next()) {
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            dictionary
+                .words
+                .entry(word.to_string())
+                .or_default()
+                .extend(parse_flags(flags, flag_type));
+        }
+
+        dictionary
+    }
+
+    /// Whether `word` is in the dictionary, also accepting a capitalized word
+    /// if its lowercase form is, as it would be at the start of a sentence.
+    pub fn check(&self, word: &str) -> bool {
+        self.lookup(word) || self.lookup(&word.to_lowercase())
+    }
+
+    fn lookup(&self, word: &str) -> bool {
+        if self.words.contains_key(word) {
+            return true;
+        }
+
+        for suffix in &self.suffixes {
+            let base = match suffix.remove(word) {
+                Some(b) => b,
+                None => continue,
+            };
+            if self.has_flags(&base, &[&suffix.flag]) {
+                return true;
+            }
+            if !suffix.cross_product {
+                continue;
+            }
+            for prefix in self.prefixes.iter().filter(|p| p.cross_product) {
+                if let Some(root) = prefix.remove(&base) {
+                    if self.has_flags(&root, &[&prefix.flag, &suffix.flag]) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        self.prefixes.iter().any(|prefix| {
+            prefix
+                .remove(word)
+                .is_some_and(|root| self.has_flags(&root, &[&prefix.flag]))
+        })
+    }
+
+    fn has_flags(&self, word: &str, flags: &[&str]) -> bool {
+        self.words
+            .get(word)
+            .is_some_and(|f| flags.iter().all(|flag| f.contains(*flag)))
+    }
+}
+
+fn parse_flags(flags: &str, flag_type: FlagType) -> Vec<String> {
+    match flag_type {
+        FlagType::Char => flags.chars().map(String::from).collect(),
+        FlagType::Long => flags
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .map(|c| c.iter().collect())
+            .collect(),
+        FlagType::Num => flags.split(',').map(|f| f.trim().to_string()).collect(),
+    }
+}
+
+/// Turns the condition of an affix rule, like `[^aeiou]y`, into a regex
+/// anchored to the end of the word for suffixes, or its start for prefixes.
+fn condition(condition: &str, is_suffix: bool) -> Regex {
+    let mut pattern = String::new();
+    let mut in_class = false;
+    for c in condition.chars() {
+        match c {
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '^' if in_class => (),
+            '.' if !in_class => {
+                pattern.push('.');
+                continue;
+            }
+            c => {
+                pattern.push_str(&regex::escape(&c.to_string()));
+                continue;
+            }
+        }
+        pattern.push(c);
+    }
+    let pattern = if is_suffix {
+        format!("(?:{})$", pattern)
+    } else {
+        format!("^(?:{})", pattern)
+    };
+    // a condition that doesn't parse can't be matched, so the rule never applies
+    Regex::new(&pattern).unwrap_or_else(|_| Regex::new(r"[^\s\S]").unwrap())
+}
+
+/// Dictionaries by the folder they were looked up in and their name,
+/// `None` if there's no such dictionary.
+type DictionaryCache = HashMap<(PathBuf, String), Option<Arc<Dictionary>>>;
+
+lazy_static! {
+    // dictionaries and word lists are loaded once and shared between all pages
+    static ref DICTIONARIES: Mutex<DictionaryCache> = Mutex::new(HashMap::new());
+    static ref WORD_LISTS: Mutex<HashMap<PathBuf, Arc<HashSet<String>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The dictionary for a `lang` attribute like `en-GB`, trying `en_GB` before `en`.
+/// None ship with the proof reader, so there's none without `dictionaries` set.
+fn dictionary_for(lang: &str, conf: &SpellingConfig) -> Option<Arc<Dictionary>> {
+    let dir = conf.dictionaries.as_deref()?;
+    let mut parts = lang.trim().split(['-', '_']);
+    let language = parts.next()?.to_lowercase();
+    let mut names = parts
+        .next()
+        .map(|region| vec![format!("{}_{}", language, region.to_uppercase())])
+        .unwrap_or_default();
+    names.push(language);
+
+    let mut cache = DICTIONARIES.lock().unwrap();
+    names.into_iter().find_map(|name| {
+        cache
+            .entry((dir.to_path_buf(), name.clone()))
+            .or_insert_with(|| load_dictionary(&name, dir).map(Arc::new))
+            .clone()
+    })
+}
+
+fn load_dictionary(name: &str, dir: &Path) -> Option<Dictionary> {
+    let aff = fs::read(dir.join(format!("{}.aff", name))).ok()?;
+    let dic = fs::read(dir.join(format!("{}.dic", name))).ok()?;
+    // older dictionaries are often in latin-1 rather than UTF-8
+    let latin1 = String::from_utf8_lossy(&aff).contains("SET ISO8859-1");
+    let decode = |bytes: &[u8]| {
+        if latin1 {
+            bytes.iter().map(|&b| b as char).collect()
+        } else {
+            String::from_utf8_lossy(bytes).to_string()
+        }
+    };
+    Some(Dictionary::parse(&decode(&aff), &decode(&dic)))
+}
+
+/// The words in a word list file, in lowercase. The config is validated on load,
+/// so a file that can't be read here is treated as empty.
+fn word_list(path: &Path) -> Arc<HashSet<String>> {
+    WORD_LISTS
+        .lock()
+        .unwrap()
+        .entry(path.to_path_buf())
+        .or_insert_with(|| {
+            let contents = fs::read_to_string(path).unwrap_or_default();
+            let words = contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect();
+            Arc::new(words)
+        })
+        .clone()
+}
+
+/// A word that's in neither the dictionary nor the word list.
+pub struct Misspelling<'a> {
+    pub word: String,
+    pub sentence: String,
+    pub element: ElementRef<'a>,
+}
+
+/// Checks the prose in the `main` and `article` elements of the page against the
+/// dictionary for its `lang` attribute. Pages without a language, or in a language
+/// there's no dictionary for, aren't checked. Every misspelled word is only reported
+/// the first time it's used.
+pub fn find_misspellings<'a>(page: &'a Page, conf: &SpellingConfig) -> Vec<Misspelling<'a>> {
+    lazy_static! {
+        static ref RE_CHUNK: Regex = Regex::new(r"\S+").unwrap();
+        // file names, domains, paths and the like aren't words
+        static ref RE_NOT_WORDS: Regex = Regex::new(r"[/@\\_=<>0-9]|\p{L}\.\p{L}").unwrap();
+        static ref RE_WORD: Regex = Regex::new(r"\p{L}+(?:['’]\p{L}+)*").unwrap();
+    }
+
+    let lang = page.document.root_element().value().attr("lang");
+    let dictionary = match lang.and_then(|lang| dictionary_for(lang, conf)) {
+        Some(d) => d,
+        None => return vec![],
+    };
+    let word_list = conf.word_list.as_deref().map(word_list).unwrap_or_default();
+
+    let prose = Prose::of_page(page);
+
+    let mut seen = HashSet::new();
+    let mut misspellings = vec![];
+    for chunk in RE_CHUNK.find_iter(&prose.text) {
+        if RE_NOT_WORDS.is_match(chunk.as_str()) {
+            continue;
+        }
+        for word in RE_WORD.find_iter(chunk.as_str()) {
+            let offset = chunk.start() + word.start();
+            let element = match prose.checked_element_at(offset) {
+                Some(e) => e,
+                None => continue,
+            };
+            let word = word.as_str().replace('’', "'");
+            // acronyms and identifiers like camelCase aren't prose either
+            if word.chars().count() < 2 || word.chars().skip(1).any(char::is_uppercase) {
+                continue;
+            }
+            if !seen.insert(word.clone()) {
+                continue;
+            }
+            if word_list.contains(&word.to_lowercase()) || dictionary.check(&word) {
+                continue;
+            }
+            misspellings.push(Misspelling {
+                sentence: prose.sentence_around(offset),
+                word,
+                element,
+            });
+        }
+    }
+    misspellings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::TempDir;
+
+    const AFF: &str = "
+SET UTF-8
+
+PFX U Y 1
+PFX U   0     un         .
+
+SFX D Y 3
+SFX D   0     d          e
+SFX D   y     ied        [^aeiou]y
+SFX D   0     ed         [^ey]
+
+SFX S N 1
+SFX S   0     s          .
+";
+
+    /// A config using an `en` dictionary with the words of the tests.
+    fn setup_conf(dir: &TempDir) -> SpellingConfig {
+        let words =
+            "again code every is like/DS not on or report/DS run second see simple spelling the";
+        let dic = format!("16\n{}\n", words.replace(' ', "\n"));
+        write(dir.path().join("en.aff"), AFF).expect("failed to write file");
+        write(dir.path().join("en.dic"), dic).expect("failed to write file");
+        SpellingConfig {
+            dictionaries: Some(dir.path().to_path_buf()),
+            ..SpellingConfig::default()
+        }
+    }
+
+    fn misspellings(html: &str, conf: &SpellingConfig) -> Vec<(String, String)> {
+        let page = Page::parse(Path::new("page.html"), html);
+        find_misspellings(&page, conf)
+            .into_iter()
+            .map(|m| (m.word, m.sentence))
+            .collect()
+    }
+
+    #[test]
+    fn test_applies_affix_rules() {
+        let dictionary = Dictionary::parse(AFF, "4\nlock/UDS\ntry/D\nlike/D\nthe\n");
+
+        for word in &[
+            "lock", "locked", "unlock", "unlocked", "locks", "tried", "liked",
+        ] {
+            assert!(dictionary.check(word), "{}", word);
+        }
+        // `S` can't be combined with a prefix, and `try` doesn't take it at all
+        for word in &["unlocks", "trys", "tryed", "likeed", "unlike", "lockd"] {
+            assert!(!dictionary.check(word), "{}", word);
+        }
+        assert!(dictionary.check("Locked"));
+        assert!(!dictionary.check("teh"));
+    }
+
+    #[test]
+    fn test_reads_long_and_numeric_flags() {
+        let long = Dictionary::parse(
+            "FLAG long\nSFX Aa Y 1\nSFX Aa 0 s .\nSFX Bb Y 1\nSFX Bb 0 ed .",
+            "1\nwalk/AaBb",
+        );
+        assert!(long.check("walks") && long.check("walked"));
+
+        let num = Dictionary::parse("FLAG num\nSFX 12 Y 1\nSFX 12 0 s .", "1\nwalk/3,12");
+        assert!(num.check("walks"));
+        assert!(!num.check("walked"));
+    }
+
+    #[test]
+    fn test_nothing_is_checked_without_dictionaries() {
+        let res = misspellings(
+            r#"<html lang="en"><main><p>Thsi is a tset</p></main></html>"#,
+            &SpellingConfig::default(),
+        );
+
+        assert!(res.is_empty(), "{:?}", res);
+    }
+
+    #[test]
+    fn test_finds_misspellings_in_prose() {
+        let dir = TempDir::new().expect("could not create temp dir");
+        let res = misspellings(
+            r#"<html lang="en"><body>
+            <nav>Navigaton isn't prose</nav>
+            <main>
+                <h1>Spelling</h1>
+                <p>Thsi is a <em>simple</em> tset. Run <code>cargo fmtt</code> on the
+                code, see <a href="/x">example.com</a> or the README.</p>
+                <p>A second tset is not reported again!</p>
+            </main>
+            </body></html>"#,
+            &setup_conf(&dir),
+        );
+
+        assert_eq!(
+            res,
+            vec![
+                ("Thsi".to_string(), "Thsi is a simple tset.".to_string()),
+                ("tset".to_string(), "Thsi is a simple tset.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uses_word_list() {
+        let dir = TempDir::new().expect("could not create temp dir");
+        let words = dir.path().join("words.txt");
+        write(&words, "# project words\nrustacean\n").expect("failed to write file");
+        let conf = SpellingConfig {
+            word_list: Some(words),
+            ..setup_conf(&dir)
+        };
+
+        let res = misspellings(
+            r#"<html lang="en"><main><p>Every Rustacean likes crabz</p></main></html>"#,
+            &conf,
+        );
+
+        assert_eq!(
+            res,
+            vec![(
+                "crabz".to_string(),
+                "Every Rustacean likes crabz".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_picks_dictionary_by_lang() {
+        let dir = TempDir::new().expect("could not create temp dir");
+        write(dir.path().join("nl.aff"), "SET UTF-8\n").expect("failed to write file");
+        write(dir.path().join("nl.dic"), "2\nhallo\nwereld\n").expect("failed to write file");
+        let conf = SpellingConfig {
+            dictionaries: Some(dir.path().to_path_buf()),
+            ..SpellingConfig::default()
+        };
+
+        let res = misspellings(
+            r#"<html lang="nl-BE"><main><p>Hallo wereld, hello</p></main></html>"#,
+            &conf,
+        );
+        assert_eq!(
+            res,
+            vec![("hello".to_string(), "Hallo wereld, hello".to_string())]
+        );
+
+        // there's no dictionary for these, so nothing can be checked
+        assert!(misspellings("<html lang=\"fy\"><main>Hoi</main></html>", &conf).is_empty());
+        assert!(misspellings("<html><main>Thsi</main></html>", &conf).is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::config::{ReaderConfig, RuleLevel, StyleRule};
+use crate::error::{CheckError, Severity};
+use crate::html::Page;
+use crate::prose::Prose;
+
+/// Runs the style rules from the config file against the page, returning what
+/// they found along with the ids of the rules that ran.
+pub fn check_style_rules(page: &Page, conf: &ReaderConfig) -> (Vec<CheckError>, Vec<&'static str>) {
+    let mut findings = vec![];
+    let mut ran = vec![];
+    let mut page_prose = None;
+
+    for rule in &conf.style_rules {
+        let default = match rule.severity {
+            RuleLevel::Off => continue,
+            RuleLevel::Warn => Severity::Warning,
+            RuleLevel::Error => Severity::Error,
+        };
+        let severity = match conf.rule_severity(rule.id, default) {
+            Some(s) => s,
+            None => continue,
+        };
+        ran.push(rule.id);
+
+        // the config is validated on load, which compiles the matcher and selector
+        let matcher = match &rule.matcher {
+            Some(m) => m,
+            None => continue,
+        };
+        let selected;
+        let prose = match &rule.parsed_selector {
+            Some(selector) => {
+                selected = Prose::of_selected(page, selector);
+                &selected
+            }
+            None => page_prose.get_or_insert_with(|| Prose::of_page(page)),
+        };
+
+        for m in matcher.find_iter(&prose.text) {
+            let element = match prose.checked_element_at(m.start()) {
+                Some(e) => e,
+                None => continue,
+            };
+            let message = match replacement(rule, m.as_str()) {
+                Some(r) => format!("{} (use \"{}\")", rule.message, r),
+                None => rule.message.clone(),
+            };
+            findings.push(CheckError::ContentError {
+                path: page.path.display().to_string(),
+                offender: m.as_str().to_string(),
+                description: format!("{} in \"{}\"", message, prose.sentence_around(m.start())),
+                location: page.locate(element),
+                rule: rule.id,
+                severity,
+            });
+        }
+    }
+
+    (findings, ran)
+}
+
+fn replacement<'r>(rule: &'r StyleRule, found: &str) -> Option<&'r str> {
+    rule.replace
+        .iter()
+        .find(|(word, _)| word.to_lowercase() == found.to_lowercase())
+        .map(|(_, r)| r.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use std::path::Path;
+
+    const STYLE_GUIDE: &str = r#"
+[[style_rules]]
+id = "preferred-spelling"
+message = "Not the house spelling"
+replace = { "e-mail" = "email", "in order to" = "to" }
+
+[[style_rules]]
+id = "eg-comma"
+message = "\"e.g.\" is followed by a comma"
+pattern = '\be\.g\.(?:[^,]|$)'
+severity = "error"
+
+[[style_rules]]
+id = "click-here"
+message = "Link text should say where it goes"
+pattern = '(?im)^[ \t]*click here[ \t]*$'
+selector = "a"
+"#;
+
+    fn findings(html: &str, conf: &ReaderConfig) -> Vec<(String, String, Severity)> {
+        let page = Page::parse(Path::new("index.html"), html);
+        check_style_rules(&page, conf)
+            .0
+            .into_iter()
+            .map(|e| (e.rule().to_string(), e.offender().to_string(), e.severity()))
+            .collect()
+    }
+
+    #[test]
+    fn test_finds_style_guide_violations() {
+        let conf = parse_config(STYLE_GUIDE).unwrap();
+        let html = r#"<html><body><main>
+            <p>Send an E-mail in order to subscribe, e.g. to the list.</p>
+            <p>Or <a href="/subscribe">click here</a>, <code>e-mail</code> is fine in code.</p>
+            </main><nav><a href="/">Click here</a></nav></body></html>"#;
+
+        assert_eq!(
+            findings(html, &conf),
+            vec![
+                (
+                    "preferred-spelling".to_string(),
+                    "E-mail".to_string(),
+                    Severity::Warning
+                ),
+                (
+                    "preferred-spelling".to_string(),
+                    "in order to".to_string(),
+                    Severity::Warning
+                ),
+                ("eg-comma".to_string(), "e.g. ".to_string(), Severity::Error),
+                (
+                    "click-here".to_string(),
+                    "click here".to_string(),
+                    Severity::Warning
+                ),
+                (
+                    "click-here".to_string(),
+                    "Click here".to_string(),
+                    Severity::Warning
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_replacement_and_sentence() {
+        let conf = parse_config(STYLE_GUIDE).unwrap();
+        let page = Page::parse(
+            Path::new("index.html"),
+            "<html><body><main><p>Hi there. Send an e-mail\n  today!</p></main></body></html>",
+        );
+        let (errors, ran) = check_style_rules(&page, &conf);
+
+        assert_eq!(ran, ["preferred-spelling", "eg-comma", "click-here"]);
+        assert_eq!(
+            errors[0].description(),
+            "Not the house spelling (use \"email\") in \"Send an e-mail today!\""
+        );
+    }
+
+    #[test]
+    fn test_rules_table_overrides_style_rule_severity() {
+        let conf = parse_config(&format!(
+            "{}\n[rules]\npreferred-spelling = \"off\"\neg-comma = \"warn\"\n",
+            STYLE_GUIDE
+        ))
+        .unwrap();
+        let html = "<html><body><main><p>An e-mail, e.g. this</p></main></body></html>";
+
+        assert_eq!(
+            findings(html, &conf),
+            vec![(
+                "eg-comma".to_string(),
+                "e.g. ".to_string(),
+                Severity::Warning
+            )]
+        );
+    }
+}
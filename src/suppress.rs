@@ -0,0 +1,304 @@
+use lazy_static::*;
+use regex::Regex;
+use scraper::{ElementRef, Selector};
+use std::collections::HashSet;
+
+use crate::dispatch::{rule_ids, RuleInfo};
+use crate::error::{Category, CheckError, Severity};
+use crate::html::Page;
+use crate::location::Location;
+use crate::ReaderConfig;
+
+pub const UNUSED_SUPPRESSION: RuleInfo = RuleInfo {
+    id: "unused-suppression",
+    name: "Suppressions silence at least one finding",
+    category: Category::Content,
+    default_severity: Severity::Warning,
+};
+
+const IGNORE_ATTR: &str = "data-proofreader-ignore";
+
+enum Scope {
+    /// `<!-- proof-reader-disable-next-line ... -->`, covers the line after the comment.
+    Line(usize),
+    /// `data-proofreader-ignore`, covers the element and everything inside it,
+    /// identified by the offsets of their start tags.
+    Elements(HashSet<usize>),
+}
+
+struct Suppression {
+    /// The rules that are silenced, all of them if empty.
+    rules: Vec<String>,
+    scope: Scope,
+    location: Option<Location>,
+    offender: String,
+    used: bool,
+}
+
+impl Suppression {
+    fn covers(&self, rule: &str, location: Option<Location>) -> bool {
+        let location = match location {
+            Some(l) => l,
+            None => return false,
+        };
+        let rule_matches = self.rules.is_empty() || self.rules.iter().any(|r| r == rule);
+
+        rule_matches
+            && match &self.scope {
+                Scope::Line(line) => location.line == *line,
+                Scope::Elements(offsets) => offsets.contains(&location.offset),
+            }
+    }
+}
+
+fn parse_rule_list(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|r| !r.is_empty())
+        .map(|r| r.to_string())
+        .collect()
+}
+
+fn find_suppressions(page: &Page) -> Vec<Suppression> {
+    lazy_static! {
+        static ref RE_DISABLE_NEXT_LINE: Regex =
+            Regex::new(r"<!--\s*proof-reader-disable-next-line\b((?s:.*?))-->").unwrap();
+    }
+
+    let mut suppressions = RE_DISABLE_NEXT_LINE
+        .captures_iter(page.source)
+        .map(|caps| {
+            let comment = caps.get(0).unwrap();
+            let end_line = page.location_of_offset(comment.end() - 1).line;
+            Suppression {
+                rules: parse_rule_list(&caps[1]),
+                scope: Scope::Line(end_line + 1),
+                location: Some(page.location_of_offset(comment.start())),
+                offender: comment.as_str().to_string(),
+                used: false,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let ignore_selector = Selector::parse(&format!("[{}]", IGNORE_ATTR)).unwrap();
+    suppressions.extend(page.document.select(&ignore_selector).map(|elt| {
+        let value = elt.value().attr(IGNORE_ATTR).unwrap_or_default();
+        let offsets = elt
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .filter_map(|e| page.locate(e))
+            .map(|l| l.offset)
+            .collect();
+        Suppression {
+            rules: parse_rule_list(value),
+            scope: Scope::Elements(offsets),
+            location: page.locate(elt),
+            offender: format!("{}=\"{}\"", IGNORE_ATTR, value),
+            used: false,
+        }
+    }));
+
+    suppressions
+}
+
+/// The suppressions in a page, for checks that run apart from `check_file`, like
+/// the one for external links. Which of them are unused is left to `apply_suppressions`.
+#[cfg(feature = "external-links")]
+pub struct Suppressions(Vec<Suppression>);
+
+#[cfg(feature = "external-links")]
+impl Suppressions {
+    pub fn of_page(page: &Page) -> Suppressions {
+        Suppressions(find_suppressions(page))
+    }
+
+    /// Whether a finding of `rule` at `location` is silenced.
+    pub fn silence(&self, rule: &str, location: Option<Location>) -> bool {
+        self.0.iter().any(|s| s.covers(rule, location))
+    }
+}
+
+/// Drops the findings silenced by suppression comments and attributes in the page,
+/// and reports the suppressions that didn't silence anything. `ran` holds the ids
+/// of the rules that were run, a suppression for a rule that was turned off can't
+/// be told apart from a stale one, so those are never reported.
+pub fn apply_suppressions(
+    page: &Page,
+    findings: Vec<CheckError>,
+    ran: &[&str],
+    conf: &ReaderConfig,
+) -> Vec<CheckError> {
+    let mut suppressions = find_suppressions(page);
+    if suppressions.is_empty() {
+        return findings;
+    }
+
+    let mut kept = findings
+        .into_iter()
+        .filter(|finding| {
+            let mut silenced = false;
+            for suppression in suppressions
+                .iter_mut()
+                .filter(|s| s.covers(finding.rule(), finding.location()))
+            {
+                suppression.used = true;
+                silenced = true;
+            }
+            !silenced
+        })
+        .collect::<Vec<_>>();
+
+    let severity =
+        match conf.rule_severity(UNUSED_SUPPRESSION.id, UNUSED_SUPPRESSION.default_severity) {
+            Some(s) => s,
+            None => return kept,
+        };
+    let mut known = rule_ids();
+    known.extend(conf.style_rules.iter().map(|r| r.id));
+
+    for suppression in suppressions.into_iter().filter(|s| !s.used) {
+        let all_ran = suppression
+            .rules
+            .iter()
+            .all(|r| ran.contains(&r.as_str()) || !known.contains(&r.as_str()));
+        if !all_ran {
+            continue;
+        }
+
+        let description = if suppression.rules.is_empty() {
+            "Suppression doesn't silence any finding".to_string()
+        } else {
+            format!(
+                "Suppression of {} doesn't silence any finding",
+                suppression.rules.join(", ")
+            )
+        };
+        kept.push(CheckError::ContentError {
+            path: page.path.display().to_string(),
+            offender: suppression.offender,
+            description,
+            location: suppression.location,
+            rule: UNUSED_SUPPRESSION.id,
+            severity,
+        });
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuleLevel;
+    use crate::html::check_html_file;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use tempfile::TempDir;
+
+    fn check_source(source: &str, conf: &ReaderConfig) -> Vec<CheckError> {
+        let test_dir = TempDir::new().expect("could not create temp dir");
+        let page_path = test_dir.path().join("page.html");
+        let mut f = File::create(&page_path).expect("failed to create file");
+        f.write_all(source.as_bytes())
+            .expect("failed to write file contents");
+
+        check_html_file(&page_path, conf, None)
+    }
+
+    fn rules_of(findings: &[CheckError]) -> Vec<&'static str> {
+        findings.iter().map(|f| f.rule()).collect()
+    }
+
+    #[test]
+    fn test_disable_next_line_comment() {
+        let source = r#"<html lang="en"><head><title>t</title></head><body>
+<!-- proof-reader-disable-next-line img-alt -->
+<img src="decorative.png">
+<img src="missing-alt.png">
+</body></html>"#;
+
+        let res = check_source(source, &ReaderConfig::default());
+
+        assert_eq!(rules_of(&res), vec!["img-alt"]);
+        assert_eq!(res[0].location().map(|l| l.line), Some(4));
+    }
+
+    #[test]
+    fn test_disable_next_line_only_silences_named_rules() {
+        let source = r#"<html lang="en"><head><title>t</title></head><body>
+<!-- proof-reader-disable-next-line no-autoplay -->
+<video autoplay><img src="a.png"></video>
+</body></html>"#;
+
+        let res = check_source(source, &ReaderConfig::default());
+
+        assert_eq!(rules_of(&res), vec!["img-alt"]);
+    }
+
+    #[test]
+    fn test_ignore_attribute_covers_descendants() {
+        let source = r#"<html lang="en"><head><title>t</title></head><body>
+<div data-proofreader-ignore="img-alt, no-autoplay">
+  <video autoplay></video>
+  <p><img src="a.png"></p>
+</div>
+<img src="b.png">
+</body></html>"#;
+
+        let res = check_source(source, &ReaderConfig::default());
+
+        assert_eq!(rules_of(&res), vec!["img-alt"]);
+        assert_eq!(res[0].location().map(|l| l.line), Some(6));
+    }
+
+    #[test]
+    fn test_empty_rule_list_silences_everything() {
+        let source = r#"<html lang="en"><head><title>t</title></head><body>
+<video autoplay data-proofreader-ignore><img src="a.png"></video>
+</body></html>"#;
+
+        let res = check_source(source, &ReaderConfig::default());
+
+        assert!(res.is_empty(), "{:?}", res);
+    }
+
+    #[test]
+    fn test_reports_unused_suppressions() {
+        let source = r#"<html lang="en"><head><title>t</title></head><body>
+<!-- proof-reader-disable-next-line img-alt -->
+<img src="a.png" alt="">
+<p data-proofreader-ignore="single-h1">text</p>
+<p data-proofreader-ignore="no-such-rule">text</p>
+</body></html>"#;
+
+        let res = check_source(source, &ReaderConfig::default());
+
+        assert_eq!(
+            rules_of(&res),
+            vec![
+                "unused-suppression",
+                "unused-suppression",
+                "unused-suppression"
+            ]
+        );
+        assert!(res.iter().all(|f| f.severity() == Severity::Warning));
+        assert_eq!(res[0].location().map(|l| (l.line, l.column)), Some((2, 1)));
+        assert_eq!(
+            res[1].description(),
+            "Suppression of single-h1 doesn't silence any finding"
+        );
+    }
+
+    #[test]
+    fn test_suppressions_of_disabled_rules_are_not_reported() {
+        let source = r#"<html lang="en"><head><title>t</title></head><body>
+<!-- proof-reader-disable-next-line img-alt -->
+<img src="a.png" alt="">
+</body></html>"#;
+        let mut conf = ReaderConfig::default();
+        conf.rules.insert("img-alt".to_string(), RuleLevel::Off);
+
+        let res = check_source(source, &conf);
+
+        assert!(res.is_empty(), "{:?}", res);
+    }
+}
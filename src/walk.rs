@@ -0,0 +1,168 @@
+use glob::Pattern;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Decides which files under the root get checked. Globs are matched against
+/// paths relative to the root, and `*` also matches `/`, so `drafts/*` covers
+/// everything below `drafts`.
+#[derive(Debug, Default)]
+pub struct FileFilter {
+    /// Files have to match one of these, unless there are none.
+    include: Vec<Pattern>,
+    /// Files and directories matching any of these are skipped, for directories
+    /// that includes everything inside them.
+    exclude: Vec<Pattern>,
+}
+
+fn compile(globs: &[String]) -> Result<Vec<Pattern>, String> {
+    globs
+        .iter()
+        .map(|g| Pattern::new(g).map_err(|e| format!("\"{}\" is not a valid glob ({})", g, e)))
+        .collect()
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<FileFilter, String> {
+        Ok(FileFilter {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Whether the file at `relative` would be checked, which it isn't if it's
+    /// in an excluded directory.
+    pub fn accepts(&self, relative: &Path) -> bool {
+        let excluded = relative
+            .ancestors()
+            .filter(|a| !a.as_os_str().is_empty())
+            .any(|a| self.is_excluded(a));
+        !excluded && self.is_included(relative)
+    }
+
+    fn is_excluded(&self, relative: &Path) -> bool {
+        self.exclude.iter().any(|p| p.matches_path(relative))
+    }
+
+    fn is_included(&self, relative: &Path) -> bool {
+        self.include.is_empty() || self.include.iter().any(|p| p.matches_path(relative))
+    }
+}
+
+/// Every file under `root` that passes `filter`. Excluded directories aren't descended into.
+pub fn files_to_check(root: &Path, filter: &FileFilter) -> Vec<PathBuf> {
+    let relative = |entry: &DirEntry| {
+        entry
+            .path()
+            .strip_prefix(root)
+            .unwrap_or(entry.path())
+            .to_path_buf()
+    };
+
+    WalkDir::new(root)
+        .into_iter()
+        // the root itself is never excluded, its relative path is empty
+        .filter_entry(|e| e.depth() == 0 || !filter.is_excluded(&relative(e)))
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir() && filter.is_included(&relative(e)))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, File};
+    use tempfile::TempDir;
+
+    fn setup_test_site() -> TempDir {
+        let site = TempDir::new().expect("could not create temp dir");
+        for file in &[
+            "index.html",
+            "style.css",
+            "posts/first.html",
+            "posts/drafts/second.html",
+            "drafts/third.html",
+            "static/img/logo.png",
+        ] {
+            let path = site.path().join(file);
+            create_dir_all(path.parent().unwrap()).expect("failed to create folder");
+            File::create(&path).expect("failed to create file");
+        }
+        site
+    }
+
+    fn walk(site: &TempDir, include: &[&str], exclude: &[&str]) -> Vec<String> {
+        let to_strings = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        let filter = FileFilter::new(&to_strings(include), &to_strings(exclude))
+            .expect("globs should be valid");
+
+        let mut files = files_to_check(site.path(), &filter)
+            .iter()
+            .map(|p| {
+                p.strip_prefix(site.path())
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_no_globs_walks_everything() {
+        let site = setup_test_site();
+
+        assert_eq!(walk(&site, &[], &[]).len(), 6);
+    }
+
+    #[test]
+    fn test_excluded_directories_are_skipped() {
+        let site = setup_test_site();
+
+        assert_eq!(
+            walk(&site, &[], &["drafts", "static"]),
+            vec![
+                "index.html",
+                "posts/drafts/second.html",
+                "posts/first.html",
+                "style.css"
+            ]
+        );
+        assert_eq!(
+            walk(&site, &[], &["posts/*"]),
+            vec![
+                "drafts/third.html",
+                "index.html",
+                "static/img/logo.png",
+                "style.css"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accepts_single_files() {
+        let exclude = vec!["drafts".to_string()];
+        let filter = FileFilter::new(&["*.html".to_string()], &exclude).unwrap();
+
+        assert!(filter.accepts(Path::new("posts/first.html")));
+        assert!(!filter.accepts(Path::new("drafts/sub/third.html")));
+        assert!(!filter.accepts(Path::new("style.css")));
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let site = setup_test_site();
+
+        assert_eq!(
+            walk(&site, &["*.html"], &["posts/drafts"]),
+            vec!["drafts/third.html", "index.html", "posts/first.html"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_glob() {
+        let res = FileFilter::new(&[], &["**a/[".to_string()]);
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use chrono::Local;
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::canonicalize;
+use std::io::{self, stdout, IsTerminal, Write};
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use crate::dispatch::check_file;
+use crate::error::{CheckError, Severity};
+use crate::site::Site;
+use crate::walk::{files_to_check, FileFilter};
+use crate::ReaderConfig;
+
+/// How long files have to stay unchanged before they're checked, so a rebuild
+/// of the site is checked once rather than file by file.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The findings of every checked file that has any.
+type Findings = BTreeMap<PathBuf, Vec<CheckError>>;
+
+/// What changed in a batch of events, with paths under `root_dir` as it was passed.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Changes {
+    changed: HashSet<PathBuf>,
+    removed: HashSet<PathBuf>,
+    /// Too much changed to tell, so everything is checked again.
+    everything: bool,
+}
+
+impl Changes {
+    /// Adds an event about a path under `watched`, the canonical form of `root`.
+    fn add(&mut self, event: DebouncedEvent, watched: &Path, root: &Path) {
+        let mut under_root = |path: &Path| match path.strip_prefix(watched) {
+            Ok(relative) if relative.as_os_str().is_empty() => {
+                self.everything = true;
+                None
+            }
+            Ok(relative) => Some(root.join(relative)),
+            Err(_) => None,
+        };
+        match event {
+            DebouncedEvent::Create(path)
+            | DebouncedEvent::Write(path)
+            | DebouncedEvent::Chmod(path) => {
+                let changed = under_root(&path);
+                self.changed.extend(changed);
+            }
+            DebouncedEvent::Remove(path) => {
+                let removed = under_root(&path);
+                self.removed.extend(removed);
+            }
+            DebouncedEvent::Rename(from, to) => {
+                let (removed, changed) = (under_root(&from), under_root(&to));
+                self.removed.extend(removed);
+                self.changed.extend(changed);
+            }
+            DebouncedEvent::Rescan => self.everything = true,
+            // notices are followed by the events above, errors are reported by the caller
+            DebouncedEvent::NoticeWrite(_)
+            | DebouncedEvent::NoticeRemove(_)
+            | DebouncedEvent::Error(..) => (),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty() && !self.everything
+    }
+}
+
+fn check_files(files: &[PathBuf], conf: &ReaderConfig, site: &Site) -> Findings {
+    files
+        .par_iter()
+        .map(|path| (path.clone(), check_file(path, conf, site)))
+        .filter(|(_, errors)| !errors.is_empty())
+        .collect()
+}
+
+fn check_everything(conf: &ReaderConfig, filter: &FileFilter) -> (Site, Findings) {
+    let site = Site::index(&conf.root_dir);
+    let findings = check_files(&files_to_check(&conf.root_dir, filter), conf, &site);
+    (site, findings)
+}
+
+/// Checks the changed files again, along with the files whose links point at them.
+fn recheck(
+    changes: &Changes,
+    conf: &ReaderConfig,
+    filter: &FileFilter,
+    site: &mut Site,
+    findings: &mut Findings,
+) {
+    for path in changes.removed.iter().chain(&changes.changed) {
+        site.reindex(path);
+    }
+    findings.retain(|path, _| !changes.removed.iter().any(|r| path.starts_with(r)));
+
+    let accepted = |path: &Path| {
+        let relative = path.strip_prefix(&conf.root_dir).unwrap_or(path);
+        path.is_file() && filter.accepts(relative)
+    };
+    let touched = changes.changed.union(&changes.removed).cloned().collect();
+    let mut files = site.dependents(&touched);
+    files.extend(changes.changed.iter().cloned());
+    let files = files
+        .into_iter()
+        .filter(|path| accepted(path))
+        .collect::<Vec<_>>();
+
+    for path in &files {
+        // checking it again records what it depends on now
+        site.take_dependencies(path);
+        findings.remove(path);
+    }
+    findings.extend(check_files(&files, conf, site));
+}
+
+/// Every finding once, sorted by file, position and rule.
+fn current_problems(findings: &Findings) -> Vec<&CheckError> {
+    let key = |f: &CheckError| {
+        (
+            f.path().to_string(),
+            f.location(),
+            f.rule(),
+            f.description(),
+            f.offender().to_string(),
+        )
+    };
+    let mut problems = findings.values().flatten().collect::<Vec<_>>();
+    problems.sort_by_cached_key(|f| key(f));
+    problems.dedup_by(|a, b| key(a) == key(b));
+    problems
+}
+
+fn print_summary(findings: &Findings) -> io::Result<()> {
+    let problems = current_problems(findings);
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity() == Severity::Error)
+        .count();
+
+    let out = stdout();
+    let mut out = out.lock();
+    // only redrawn in place in a terminal, piped output keeps every summary
+    if out.is_terminal() {
+        write!(out, "\x1B[2J\x1B[H")?;
+    }
+    for problem in &problems {
+        writeln!(out, "{}", problem)?;
+    }
+    writeln!(
+        out,
+        "[{}] {} problems ({} errors, {} warnings), watching for changes",
+        Local::now().format("%H:%M:%S"),
+        problems.len(),
+        errors,
+        problems.len() - errors
+    )?;
+    out.flush()
+}
+
+/// Checks the site whenever files under `root_dir` change, until interrupted.
+pub fn watch(conf: &ReaderConfig, filter: &FileFilter) -> Result<(), String> {
+    let watched = canonicalize(&conf.root_dir)
+        .map_err(|e| format!("Could not watch {}: {}", conf.root_dir.display(), e))?;
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, DEBOUNCE).map_err(|e| format!("Could not watch: {}", e))?;
+    // watching before the first check, so nothing that changes during it is missed
+    watcher
+        .watch(&watched, RecursiveMode::Recursive)
+        .map_err(|e| format!("Could not watch {}: {}", conf.root_dir.display(), e))?;
+
+    let report_error = |e: io::Error| format!("Could not write summary: {}", e);
+    let (mut site, mut findings) = check_everything(conf, filter);
+    print_summary(&findings).map_err(report_error)?;
+
+    // fails once the watcher is gone
+    while let Ok(event) = rx.recv() {
+        let mut changes = Changes::default();
+        for event in once(event).chain(rx.try_iter()) {
+            if let DebouncedEvent::Error(e, path) = &event {
+                match path {
+                    Some(path) => eprintln!("Error watching {}: {}", path.display(), e),
+                    None => eprintln!("Error watching: {}", e),
+                }
+            }
+            changes.add(event, &watched, &conf.root_dir);
+        }
+        if changes.is_empty() {
+            continue;
+        }
+
+        // a new folder's files don't get events of their own
+        if changes.everything || changes.changed.iter().any(|p| p.is_dir()) {
+            (site, findings) = check_everything(conf, filter);
+        } else {
+            recheck(&changes, conf, filter, &mut site, &mut findings);
+        }
+        print_summary(&findings).map_err(report_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::TempDir;
+
+    fn page(body: &str) -> String {
+        format!(
+            r#"<html lang="en"><head><title>Page</title></head><body><main><h1>Page</h1>{}</main></body></html>"#,
+            body
+        )
+    }
+
+    #[test]
+    fn test_events_map_to_root() {
+        let watched = Path::new("/srv/site/public");
+        let root = Path::new("public");
+        let mut changes = Changes::default();
+
+        changes.add(
+            DebouncedEvent::Write(watched.join("index.html")),
+            watched,
+            root,
+        );
+        changes.add(
+            DebouncedEvent::Rename(watched.join("old.html"), watched.join("new.html")),
+            watched,
+            root,
+        );
+        changes.add(
+            DebouncedEvent::Remove(PathBuf::from("/tmp/x")),
+            watched,
+            root,
+        );
+        changes.add(
+            DebouncedEvent::NoticeWrite(watched.join("index.html")),
+            watched,
+            root,
+        );
+
+        assert_eq!(
+            changes,
+            Changes {
+                changed: vec![root.join("index.html"), root.join("new.html")]
+                    .into_iter()
+                    .collect(),
+                removed: vec![root.join("old.html")].into_iter().collect(),
+                everything: false,
+            }
+        );
+
+        changes.add(DebouncedEvent::Remove(watched.to_path_buf()), watched, root);
+        assert!(changes.everything);
+    }
+
+    #[test]
+    fn test_pages_linking_to_changed_files_are_checked_again() {
+        let site_dir = TempDir::new().expect("could not create temp dir");
+        let root = site_dir.path().to_path_buf();
+        write(
+            root.join("index.html"),
+            page(r#"<a href="about.html">About</a>"#),
+        )
+        .expect("failed to write file");
+        let conf = ReaderConfig {
+            root_dir: root.clone(),
+            ..ReaderConfig::default()
+        };
+        let filter = FileFilter::default();
+
+        let (mut site, mut findings) = check_everything(&conf, &filter);
+        let rules = |findings: &Findings| {
+            current_problems(findings)
+                .iter()
+                .map(|f| f.rule())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rules(&findings), vec!["internal-link"]);
+
+        write(root.join("about.html"), page("<img src=\"\">")).expect("failed to write file");
+        let changes = Changes {
+            changed: once(root.join("about.html")).collect(),
+            ..Changes::default()
+        };
+        recheck(&changes, &conf, &filter, &mut site, &mut findings);
+        assert_eq!(rules(&findings), vec!["img-alt"]);
+        assert!(findings.contains_key(&root.join("about.html")));
+    }
+
+    #[test]
+    fn test_problems_are_sorted_and_deduplicated() {
+        let finding = |path: &str, rule: &'static str| CheckError::ContentError {
+            path: path.to_string(),
+            offender: "x".to_string(),
+            description: "Problem".to_string(),
+            location: None,
+            rule,
+            severity: Severity::Warning,
+        };
+        let mut findings = Findings::new();
+        findings.insert(
+            PathBuf::from("b.html"),
+            vec![finding("b.html", "title"), finding("b.html", "img-alt")],
+        );
+        findings.insert(
+            PathBuf::from("a.md"),
+            vec![finding("a.md", "draft"), finding("a.md", "draft")],
+        );
+
+        let problems = current_problems(&findings)
+            .iter()
+            .map(|f| (f.path(), f.rule()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            problems,
+            vec![
+                ("a.md", "draft"),
+                ("b.html", "img-alt"),
+                ("b.html", "title")
+            ]
+        );
+    }
+}